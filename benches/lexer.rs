@@ -0,0 +1,38 @@
+// Lexes generated sources of growing size, the time per MB should stay flat
+// since the lexer runs in linear time.
+//
+//   cargo bench --bench lexer
+
+use rust_interpreter::{Lexer, Token};
+use std::time::Instant;
+
+const SNIPPET: &str = r#"
+let fibonacci = fn(n) {
+    // Naïve recursive definition, ∀ n ≥ 0
+    if (n < 2) { return n; }
+    fibonacci(n - 1) + fibonacci(n - 2);
+};
+/* Größe: ☃ */ let résultat = fibonacci(0x1F) * 1_000 + 2.5e3 >> 2;
+"#;
+
+fn main() {
+    for megabytes in [1, 2, 4, 8] {
+        let size = megabytes * 1024 * 1024;
+        let source = SNIPPET.repeat(size / SNIPPET.len() + 1);
+
+        let start = Instant::now();
+
+        let mut lexer = Lexer::new(source);
+        let mut tokens = 0;
+        while lexer.next_token() != Token::Eof {
+            tokens += 1;
+        }
+
+        let elapsed = start.elapsed();
+
+        println!(
+            "{megabytes} MB: {tokens} tokens in {elapsed:.2?} ({:.2?} per MB)",
+            elapsed / megabytes as u32
+        );
+    }
+}
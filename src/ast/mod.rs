@@ -1,10 +1,15 @@
-use crate::token::Token;
+pub mod visitor;
+
+pub use visitor::{Visitor, VisitorMut};
+
+use crate::token::{Span, Token};
+use num_bigint::BigInt;
 use std::str::FromStr;
 
 // Program is the root node of the AST
-#[derive(Default)]
+#[derive(Debug, Default, PartialEq)]
 pub struct Program {
-    pub(crate) statements: Vec<Statement>,
+    pub statements: Vec<Statement>,
 }
 
 impl std::fmt::Display for Program {
@@ -22,6 +27,7 @@ impl std::fmt::Display for Program {
 pub enum Statement {
     Let { name: String, value: Expression },
     Return(Expression),
+    Throw(Expression),
     Expression(Expression),
     Block(Vec<Statement>),
 }
@@ -31,6 +37,7 @@ impl std::fmt::Display for Statement {
         match self {
             Statement::Let { name, value } => write!(f, "let {name} = {value};"),
             Statement::Return(value) => write!(f, "return {value};"),
+            Statement::Throw(value) => write!(f, "throw {value};"),
             Statement::Expression(value) => write!(f, "{value}"),
             Statement::Block(statements) => {
                 for statement in statements {
@@ -48,6 +55,9 @@ pub enum Expression {
     None,
     Identifier(String),
     Int(i64),
+    BigInt(BigInt),
+    Float(f64),
+    Str(String),
     // -x
     Prefix {
         operator: PrefixOperator,
@@ -69,19 +79,37 @@ pub enum Expression {
         parameters: Vec<Expression>,
         body: Vec<Statement>,
     },
-    // Function call
+    // try { } catch (e) { } finally { }, with a catch or a finally or both.
+    // The catch holds the name the error is bound to
+    Try {
+        body: Vec<Statement>,
+        catch: Option<(String, Vec<Statement>)>,
+        finally: Option<Vec<Statement>>,
+    },
+    // x?, the value of an ok or a return of an err
+    Propagate(Box<Expression>),
+    // Function call, the span is the one of the whole call and is only used to
+    // report runtime errors
     FunctionCall {
         function: Box<Expression>,
         arguments: Vec<Expression>,
+        span: Location,
     },
 }
 
+// Where a node is in the source
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Location(pub Span);
+
 impl std::fmt::Display for Expression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Expression::None => write!(f, "A"),
             Expression::Identifier(name) => write!(f, "{name}"),
             Expression::Int(value) => write!(f, "{value}"),
+            Expression::BigInt(value) => write!(f, "{value}"),
+            Expression::Float(value) => write!(f, "{value:?}"),
+            Expression::Str(value) => write!(f, "\"{}\"", escape_string(value)),
             Expression::Prefix { operator, right } => write!(f, "({operator}{right})"),
             Expression::Infix {
                 left,
@@ -110,7 +138,7 @@ impl std::fmt::Display for Expression {
                 Ok(())
             }
             Expression::Function { parameters, body } => {
-                write!(f, "{}(", Token::Function.to_string())?;
+                write!(f, "{}(", Token::Function)?;
 
                 for (i, param) in parameters.iter().enumerate() {
                     write!(f, "{}", param)?;
@@ -125,9 +153,39 @@ impl std::fmt::Display for Expression {
                 }
                 Ok(())
             }
+            Expression::Propagate(value) => write!(f, "({value}?)"),
+            Expression::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                write!(f, "try ")?;
+
+                for statement in body {
+                    write!(f, "{statement}")?;
+                }
+
+                if let Some((name, handler)) = catch {
+                    write!(f, "catch ({name}) ")?;
+
+                    for statement in handler {
+                        write!(f, "{statement}")?;
+                    }
+                }
+
+                if let Some(finally) = finally {
+                    write!(f, "finally ")?;
+
+                    for statement in finally {
+                        write!(f, "{statement}")?;
+                    }
+                }
+                Ok(())
+            }
             Expression::FunctionCall {
                 function,
                 arguments,
+                ..
             } => {
                 write!(f, "{}(", function)?;
 
@@ -143,10 +201,30 @@ impl std::fmt::Display for Expression {
     }
 }
 
+// Inverse of the escapes handled by the lexer
+pub(crate) fn escape_string(str: &str) -> String {
+    let mut escaped = String::with_capacity(str.len());
+
+    for ch in str.chars() {
+        match ch {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            _ => escaped.push(ch),
+        }
+    }
+
+    escaped
+}
+
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) enum PrefixOperator {
+pub enum PrefixOperator {
     Bang,
     Minus,
+    BitNot,
 }
 
 impl std::fmt::Display for PrefixOperator {
@@ -154,6 +232,7 @@ impl std::fmt::Display for PrefixOperator {
         match self {
             PrefixOperator::Bang => write!(f, "!"),
             PrefixOperator::Minus => write!(f, "-"),
+            PrefixOperator::BitNot => write!(f, "~"),
         }
     }
 }
@@ -165,6 +244,7 @@ impl TryFrom<&Token> for PrefixOperator {
         match value {
             Token::Bang => Ok(Self::Bang),
             Token::Minus => Ok(Self::Minus),
+            Token::Tilde => Ok(Self::BitNot),
             _ => Err(()),
         }
     }
@@ -180,6 +260,11 @@ pub enum InfixOperator {
     LessThan,
     Equal,
     NotEqual,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
 }
 
 impl std::fmt::Display for InfixOperator {
@@ -193,6 +278,11 @@ impl std::fmt::Display for InfixOperator {
             InfixOperator::LessThan => write!(f, "<"),
             InfixOperator::Equal => write!(f, "=="),
             InfixOperator::NotEqual => write!(f, "!="),
+            InfixOperator::BitAnd => write!(f, "&"),
+            InfixOperator::BitOr => write!(f, "|"),
+            InfixOperator::BitXor => write!(f, "^"),
+            InfixOperator::ShiftLeft => write!(f, "<<"),
+            InfixOperator::ShiftRight => write!(f, ">>"),
         }
     }
 }
@@ -208,11 +298,29 @@ impl From<&Token> for InfixOperator {
             Token::LessThan => Self::LessThan,
             Token::Eq => Self::Equal,
             Token::NotEq => Self::NotEqual,
+            Token::Ampersand => Self::BitAnd,
+            Token::Pipe => Self::BitOr,
+            Token::Caret => Self::BitXor,
+            Token::ShiftLeft => Self::ShiftLeft,
+            Token::ShiftRight => Self::ShiftRight,
             _ => panic!("Invalid token"),
         }
     }
 }
 
+impl InfixOperator {
+    pub(crate) fn is_bitwise(&self) -> bool {
+        matches!(
+            self,
+            InfixOperator::BitAnd
+                | InfixOperator::BitOr
+                | InfixOperator::BitXor
+                | InfixOperator::ShiftLeft
+                | InfixOperator::ShiftRight
+        )
+    }
+}
+
 // Used for tests
 impl FromStr for InfixOperator {
     type Err = ();
@@ -227,11 +335,35 @@ impl FromStr for InfixOperator {
             "<" => Ok(Self::LessThan),
             "==" => Ok(Self::Equal),
             "!=" => Ok(Self::NotEqual),
+            "&" => Ok(Self::BitAnd),
+            "|" => Ok(Self::BitOr),
+            "^" => Ok(Self::BitXor),
+            "<<" => Ok(Self::ShiftLeft),
+            ">>" => Ok(Self::ShiftRight),
             _ => Err(()),
         }
     }
 }
 
+// Tests comparing programs laid out differently compare them without the
+// locations of their nodes
+#[cfg(test)]
+pub(crate) fn without_locations(mut program: Program) -> Program {
+    struct Strip;
+
+    impl VisitorMut for Strip {
+        fn visit_expression_mut(&mut self, expression: &mut Expression) {
+            if let Expression::FunctionCall { span, .. } = expression {
+                *span = Location::default();
+            }
+            visitor::walk_expression_mut(self, expression);
+        }
+    }
+
+    Strip.visit_program_mut(&mut program);
+    program
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,316 @@
+use super::{Expression, InfixOperator, PrefixOperator, Program, Statement};
+
+// Read-only traversal of the AST. Each visit_ method defaults to the matching
+// walk_ function, which visits the children in source order. An override that
+// still wants the children visited calls the walk_ function itself
+pub trait Visitor: Sized {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program)
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement)
+    }
+
+    // Bodies of if branches, functions, try clauses and block statements
+    fn visit_block(&mut self, block: &[Statement]) {
+        walk_block(self, block)
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression)
+    }
+
+    // Identifiers in expressions and function parameters, not let names
+    fn visit_identifier(&mut self, _name: &str) {}
+
+    fn visit_prefix_operator(&mut self, _operator: &PrefixOperator) {}
+
+    fn visit_infix_operator(&mut self, _operator: &InfixOperator) {}
+}
+
+pub fn walk_program<V: Visitor>(visitor: &mut V, program: &Program) {
+    for statement in &program.statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_statement<V: Visitor>(visitor: &mut V, statement: &Statement) {
+    match statement {
+        Statement::Let { name: _, value } => visitor.visit_expression(value),
+        Statement::Return(value) | Statement::Throw(value) | Statement::Expression(value) => {
+            visitor.visit_expression(value)
+        }
+        Statement::Block(block) => visitor.visit_block(block),
+    }
+}
+
+pub fn walk_block<V: Visitor>(visitor: &mut V, block: &[Statement]) {
+    for statement in block {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_expression<V: Visitor>(visitor: &mut V, expression: &Expression) {
+    match expression {
+        Expression::None
+        | Expression::Int(_)
+        | Expression::BigInt(_)
+        | Expression::Float(_)
+        | Expression::Str(_)
+        | Expression::Bool(_) => {}
+        Expression::Identifier(name) => visitor.visit_identifier(name),
+        Expression::Prefix { operator, right } => {
+            visitor.visit_prefix_operator(operator);
+            visitor.visit_expression(right);
+        }
+        Expression::Infix {
+            left,
+            operator,
+            right,
+        } => {
+            visitor.visit_expression(left);
+            visitor.visit_infix_operator(operator);
+            visitor.visit_expression(right);
+        }
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+        } => {
+            visitor.visit_expression(condition);
+            visitor.visit_block(consequence);
+            if let Some(alternative) = alternative {
+                visitor.visit_block(alternative);
+            }
+        }
+        Expression::Function { parameters, body } => {
+            for parameter in parameters {
+                visitor.visit_expression(parameter);
+            }
+            visitor.visit_block(body);
+        }
+        Expression::Propagate(value) => visitor.visit_expression(value),
+        Expression::Try {
+            body,
+            catch,
+            finally,
+        } => {
+            visitor.visit_block(body);
+            if let Some((_, handler)) = catch {
+                visitor.visit_block(handler);
+            }
+            if let Some(finally) = finally {
+                visitor.visit_block(finally);
+            }
+        }
+        Expression::FunctionCall {
+            function,
+            arguments,
+            ..
+        } => {
+            visitor.visit_expression(function);
+            for argument in arguments {
+                visitor.visit_expression(argument);
+            }
+        }
+    }
+}
+
+// Same as Visitor, with mutable access to rewrite the nodes in place. Blocks
+// are given as Vec so that statements can be added or removed
+pub trait VisitorMut: Sized {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program)
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement)
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Vec<Statement>) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression)
+    }
+
+    fn visit_identifier_mut(&mut self, _name: &mut String) {}
+
+    fn visit_prefix_operator_mut(&mut self, _operator: &mut PrefixOperator) {}
+
+    fn visit_infix_operator_mut(&mut self, _operator: &mut InfixOperator) {}
+}
+
+pub fn walk_program_mut<V: VisitorMut>(visitor: &mut V, program: &mut Program) {
+    for statement in &mut program.statements {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+pub fn walk_statement_mut<V: VisitorMut>(visitor: &mut V, statement: &mut Statement) {
+    match statement {
+        Statement::Let { name: _, value } => visitor.visit_expression_mut(value),
+        Statement::Return(value) | Statement::Throw(value) | Statement::Expression(value) => {
+            visitor.visit_expression_mut(value)
+        }
+        Statement::Block(block) => visitor.visit_block_mut(block),
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut>(visitor: &mut V, block: &mut Vec<Statement>) {
+    for statement in block {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut>(visitor: &mut V, expression: &mut Expression) {
+    match expression {
+        Expression::None
+        | Expression::Int(_)
+        | Expression::BigInt(_)
+        | Expression::Float(_)
+        | Expression::Str(_)
+        | Expression::Bool(_) => {}
+        Expression::Identifier(name) => visitor.visit_identifier_mut(name),
+        Expression::Prefix { operator, right } => {
+            visitor.visit_prefix_operator_mut(operator);
+            visitor.visit_expression_mut(right);
+        }
+        Expression::Infix {
+            left,
+            operator,
+            right,
+        } => {
+            visitor.visit_expression_mut(left);
+            visitor.visit_infix_operator_mut(operator);
+            visitor.visit_expression_mut(right);
+        }
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+        } => {
+            visitor.visit_expression_mut(condition);
+            visitor.visit_block_mut(consequence);
+            if let Some(alternative) = alternative {
+                visitor.visit_block_mut(alternative);
+            }
+        }
+        Expression::Function { parameters, body } => {
+            for parameter in parameters {
+                visitor.visit_expression_mut(parameter);
+            }
+            visitor.visit_block_mut(body);
+        }
+        Expression::Propagate(value) => visitor.visit_expression_mut(value),
+        Expression::Try {
+            body,
+            catch,
+            finally,
+        } => {
+            visitor.visit_block_mut(body);
+            if let Some((_, handler)) = catch {
+                visitor.visit_block_mut(handler);
+            }
+            if let Some(finally) = finally {
+                visitor.visit_block_mut(finally);
+            }
+        }
+        Expression::FunctionCall {
+            function,
+            arguments,
+            ..
+        } => {
+            visitor.visit_expression_mut(function);
+            for argument in arguments {
+                visitor.visit_expression_mut(argument);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::without_locations, lexer::Lexer, parser::Parser};
+
+    fn parse(input: &str) -> Program {
+        let mut parser = Parser::new(Lexer::new(input.to_string()));
+        parser.parse_program().unwrap()
+    }
+
+    #[derive(Default)]
+    struct Identifiers(Vec<String>);
+
+    impl Visitor for Identifiers {
+        fn visit_identifier(&mut self, name: &str) {
+            self.0.push(name.to_string());
+        }
+    }
+
+    // Counts the operators, skipping the bodies of functions
+    #[derive(Default)]
+    struct Operators(usize);
+
+    impl Visitor for Operators {
+        fn visit_expression(&mut self, expression: &Expression) {
+            if !matches!(expression, Expression::Function { .. }) {
+                walk_expression(self, expression);
+            }
+        }
+
+        fn visit_prefix_operator(&mut self, _operator: &PrefixOperator) {
+            self.0 += 1;
+        }
+
+        fn visit_infix_operator(&mut self, _operator: &InfixOperator) {
+            self.0 += 1;
+        }
+    }
+
+    #[test]
+    fn visitor() {
+        let program =
+            parse("let f = fn(a, b) { if (a > b) { a } else { -b } }; return f(x, y + 1) * -z;");
+
+        let mut identifiers = Identifiers::default();
+        identifiers.visit_program(&program);
+        assert_eq!(
+            identifiers.0,
+            vec!["a", "b", "a", "b", "a", "b", "f", "x", "y", "z"]
+        );
+
+        let mut operators = Operators::default();
+        operators.visit_program(&program);
+        assert_eq!(operators.0, 3);
+    }
+
+    // Renames identifiers and drops the statements after a return
+    struct Rewrite;
+
+    impl VisitorMut for Rewrite {
+        fn visit_identifier_mut(&mut self, name: &mut String) {
+            name.make_ascii_uppercase();
+        }
+
+        fn visit_block_mut(&mut self, block: &mut Vec<Statement>) {
+            if let Some(i) = block.iter().position(|s| matches!(s, Statement::Return(_))) {
+                block.truncate(i + 1);
+            }
+            walk_block_mut(self, block);
+        }
+    }
+
+    #[test]
+    fn visitor_mut() {
+        let mut program = parse("let f = fn(a) { return a + b; a; }; f(c)");
+        Rewrite.visit_program_mut(&mut program);
+
+        assert_eq!(
+            without_locations(program),
+            without_locations(parse("let f = fn(A) { return A + B; }; F(C)"))
+        );
+    }
+}
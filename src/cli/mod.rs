@@ -0,0 +1,342 @@
+use crate::{emit::Emit, evaluation::error::explain, format::format, Limits, Repl};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    process::ExitCode,
+    str::FromStr,
+    time::Duration,
+};
+
+const USAGE: &str = "\
+Usage:
+    rust-interpreter [--max-call-depth=N] [--max-steps=N] [--timeout-ms=N] [--max-allocated=N]
+                                          Start the REPL. Calls nested deeper than
+                                          --max-call-depth fail with a stack overflow
+                                          error, tail calls excepted (default 10000).
+                                          Each input fails after evaluating --max-steps
+                                          statements and expressions, or after running
+                                          for --timeout-ms milliseconds. Inputs fail
+                                          once the session allocated --max-allocated
+                                          bytes in total, freed ones included
+    rust-interpreter fmt [--check] [FILE...]
+                                          Format the files in place, or stdin to stdout.
+                                          With --check, only report the files that
+                                          are not formatted and fail if there are any
+    rust-interpreter explain CODE         Describe the runtime error with the code, such
+                                          as E0001
+    rust-interpreter --emit=ast-json|sexpr|dot [FILE]
+                                          Dump the AST of the file, or stdin, with the
+                                          source span of each node except in dot";
+
+// Command line entry point, the arguments exclude the program name
+#[derive(Debug, PartialEq)]
+pub enum Cli {
+    Repl { limits: Limits },
+    Fmt { check: bool, files: Vec<String> },
+    // Reads stdin when there is no file
+    Emit { emit: Emit, file: Option<String> },
+    Explain { code: String },
+    Help,
+}
+
+impl Cli {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter();
+
+        match args.next().as_deref() {
+            None => Ok(Cli::Repl {
+                limits: Limits::default(),
+            }),
+            Some(arg) if arg.starts_with("--max-") || arg.starts_with("--timeout-") => {
+                parse_limits(std::iter::once(arg.to_string()).chain(args))
+            }
+            Some("-h" | "--help" | "help") => Ok(Cli::Help),
+            Some("fmt") => {
+                let mut check = false;
+                let mut files = vec![];
+                for arg in args {
+                    match arg.as_str() {
+                        "--check" => check = true,
+                        flag if flag.starts_with('-') => {
+                            return Err(format!("Unknown option {}", flag))
+                        }
+                        _ => files.push(arg),
+                    }
+                }
+                Ok(Cli::Fmt { check, files })
+            }
+            Some(arg) if arg.starts_with("--emit=") => {
+                let emit = &arg["--emit=".len()..];
+                let Ok(emit) = Emit::from_str(emit) else {
+                    return Err(format!(
+                        "Unknown emit format {}, expected {}, {} or {}",
+                        emit,
+                        Emit::AstJson,
+                        Emit::Sexpr,
+                        Emit::Dot
+                    ));
+                };
+                let file = args.next();
+                match args.next() {
+                    Some(arg) => Err(format!("Unexpected argument {}", arg)),
+                    None => Ok(Cli::Emit { emit, file }),
+                }
+            }
+            Some("explain") => match (args.next(), args.next()) {
+                (Some(code), None) => Ok(Cli::Explain { code }),
+                (None, _) => Err("Missing error code".to_string()),
+                (Some(_), Some(arg)) => Err(format!("Unexpected argument {}", arg)),
+            },
+            Some(command) => Err(format!("Unknown command {}", command)),
+        }
+    }
+
+    pub fn run(self) -> ExitCode {
+        match self {
+            Cli::Repl { limits } => {
+                Repl::new(limits).start();
+                ExitCode::SUCCESS
+            }
+            Cli::Help => {
+                println!("{}", USAGE);
+                ExitCode::SUCCESS
+            }
+            Cli::Emit { emit, file } => emit_source(emit, file),
+            Cli::Explain { code } => match explain(&code) {
+                Some(explanation) => {
+                    println!("{}", explanation);
+                    ExitCode::SUCCESS
+                }
+                None => {
+                    eprintln!("Unknown error code {}", code);
+                    ExitCode::FAILURE
+                }
+            },
+            Cli::Fmt { check, files } if files.is_empty() => fmt_stdin(check),
+            Cli::Fmt { check, files } => {
+                // Every file is processed even after a failure
+                let failures = files.iter().filter(|file| !fmt_file(file, check)).count();
+                match failures {
+                    0 => ExitCode::SUCCESS,
+                    _ => ExitCode::FAILURE,
+                }
+            }
+        }
+    }
+}
+
+// Options of the REPL
+fn parse_limits(args: impl Iterator<Item = String>) -> Result<Cli, String> {
+    let mut limits = Limits::default();
+    for arg in args {
+        let (option, value) = arg.split_once('=').unwrap_or((&arg, ""));
+        let invalid = |_| format!("Invalid value for {}: {}", option, value);
+        match option {
+            "--max-call-depth" => limits.max_call_depth = value.parse().map_err(invalid)?,
+            "--max-steps" => limits.max_steps = Some(value.parse().map_err(invalid)?),
+            "--max-allocated" => limits.max_allocated = Some(value.parse().map_err(invalid)?),
+            "--timeout-ms" => {
+                limits.timeout = Some(Duration::from_millis(value.parse().map_err(invalid)?))
+            }
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+    Ok(Cli::Repl { limits })
+}
+
+fn emit_source(emit: Emit, file: Option<String>) -> ExitCode {
+    let name = file.as_deref().unwrap_or("<stdin>");
+    let input = match &file {
+        Some(file) => fs::read_to_string(file),
+        None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input).map(|_| input)
+        }
+    };
+    let input = match input {
+        Ok(input) => input,
+        Err(err) => {
+            eprintln!("Failed to read {}: {}", name, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match emit.source(input) {
+        Ok(out) => {
+            print!("{}", out);
+            ExitCode::SUCCESS
+        }
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{}: {}", name, error);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn fmt_stdin(check: bool) -> ExitCode {
+    let mut input = String::new();
+    if let Err(err) = io::stdin().read_to_string(&mut input) {
+        eprintln!("Failed to read stdin: {}", err);
+        return ExitCode::FAILURE;
+    }
+
+    match format_source("<stdin>", &input) {
+        Some(formatted) if check && formatted != input => {
+            eprintln!("<stdin> is not formatted");
+            ExitCode::FAILURE
+        }
+        Some(_) if check => ExitCode::SUCCESS,
+        Some(formatted) => match io::stdout().write_all(formatted.as_bytes()) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(_) => ExitCode::FAILURE,
+        },
+        None => ExitCode::FAILURE,
+    }
+}
+
+// Returns whether the file is formatted, or was formatted successfully
+fn fmt_file(file: &str, check: bool) -> bool {
+    let input = match fs::read_to_string(file) {
+        Ok(input) => input,
+        Err(err) => {
+            eprintln!("Failed to read {}: {}", file, err);
+            return false;
+        }
+    };
+
+    let Some(formatted) = format_source(file, &input) else {
+        return false;
+    };
+
+    match (formatted == input, check) {
+        (true, _) => true,
+        (false, true) => {
+            eprintln!("{} is not formatted", file);
+            false
+        }
+        (false, false) => match fs::write(file, formatted) {
+            Ok(()) => true,
+            Err(err) => {
+                eprintln!("Failed to write {}: {}", file, err);
+                false
+            }
+        },
+    }
+}
+
+fn format_source(name: &str, input: &str) -> Option<String> {
+    match format(input.to_string()) {
+        Ok(formatted) => Some(formatted),
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{}: {}", name, error);
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_args() {
+        let tests = vec![
+            (
+                vec![],
+                Ok(Cli::Repl {
+                    limits: Limits::default(),
+                }),
+            ),
+            (
+                vec!["--max-call-depth=100"],
+                Ok(Cli::Repl {
+                    limits: Limits {
+                        max_call_depth: 100,
+                        ..Default::default()
+                    },
+                }),
+            ),
+            (
+                vec![
+                    "--max-steps=1000",
+                    "--timeout-ms=250",
+                    "--max-allocated=4096",
+                ],
+                Ok(Cli::Repl {
+                    limits: Limits {
+                        max_steps: Some(1000),
+                        timeout: Some(Duration::from_millis(250)),
+                        max_allocated: Some(4096),
+                        ..Default::default()
+                    },
+                }),
+            ),
+            (
+                vec!["--max-call-depth=-1"],
+                Err("Invalid value for --max-call-depth: -1".to_string()),
+            ),
+            (
+                vec!["--max-call-depth=10", "fmt"],
+                Err("Unexpected argument fmt".to_string()),
+            ),
+            (vec!["--help"], Ok(Cli::Help)),
+            (
+                vec!["fmt"],
+                Ok(Cli::Fmt {
+                    check: false,
+                    files: vec![],
+                }),
+            ),
+            (
+                vec!["fmt", "a.mk", "--check", "b.mk"],
+                Ok(Cli::Fmt {
+                    check: true,
+                    files: vec!["a.mk".to_string(), "b.mk".to_string()],
+                }),
+            ),
+            (
+                vec!["fmt", "--fix"],
+                Err("Unknown option --fix".to_string()),
+            ),
+            (
+                vec!["--emit=sexpr", "a.mk"],
+                Ok(Cli::Emit {
+                    emit: Emit::Sexpr,
+                    file: Some("a.mk".to_string()),
+                }),
+            ),
+            (
+                vec!["--emit=ast-json"],
+                Ok(Cli::Emit {
+                    emit: Emit::AstJson,
+                    file: None,
+                }),
+            ),
+            (
+                vec!["--emit=xml"],
+                Err("Unknown emit format xml, expected ast-json, sexpr or dot".to_string()),
+            ),
+            (
+                vec!["explain", "E0008"],
+                Ok(Cli::Explain {
+                    code: "E0008".to_string(),
+                }),
+            ),
+            (vec!["explain"], Err("Missing error code".to_string())),
+            (
+                vec!["explain", "E0001", "E0002"],
+                Err("Unexpected argument E0002".to_string()),
+            ),
+            (vec!["run"], Err("Unknown command run".to_string())),
+        ];
+
+        for (args, expected) in tests {
+            let args = args.into_iter().map(String::from);
+            assert_eq!(Cli::parse(args), expected);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use super::{SyntaxElement, SyntaxKind, SyntaxNode};
+use crate::{
+    ast::{Expression, InfixOperator, Location, PrefixOperator, Program, Statement},
+    token::{Span, Token},
+};
+
+// Source spans of the AST nodes, one per statement and expression in the
+// order Visitor walks them: a node comes before its children, and children in
+// source order. Parentheses are not nodes, a parenthesized expression spans
+// its inner expression only
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceMap {
+    pub spans: Vec<Span>,
+}
+
+// Turns a syntax tree into the AST the parser would have produced for the
+// same source, statements in Error nodes are left out
+pub fn lower(program: &SyntaxNode) -> Program {
+    lower_with_spans(program).0
+}
+
+pub fn lower_with_spans(program: &SyntaxNode) -> (Program, SourceMap) {
+    let mut lowering = Lowering { spans: vec![] };
+    let statements = lowering.statements(program);
+    (
+        Program { statements },
+        SourceMap {
+            spans: lowering.spans,
+        },
+    )
+}
+
+struct Lowering {
+    spans: Vec<Span>,
+}
+
+impl Lowering {
+    fn span(&mut self, node: &SyntaxNode) {
+        self.spans.push(node.span().unwrap_or_default());
+    }
+
+    fn statements(&mut self, node: &SyntaxNode) -> Vec<Statement> {
+        node.nodes()
+            .filter_map(|statement| self.statement(statement))
+            .collect()
+    }
+
+    fn statement(&mut self, node: &SyntaxNode) -> Option<Statement> {
+        let value = node.nodes().next();
+
+        match node.kind {
+            SyntaxKind::LetStatement => {
+                let name = node.tokens().find_map(|token| match &token.token {
+                    Token::Ident(name) => Some(name.clone()),
+                    _ => None,
+                })?;
+                self.span(node);
+                Some(Statement::Let {
+                    name,
+                    value: self.child_expression(node, value),
+                })
+            }
+            SyntaxKind::ReturnStatement => {
+                self.span(node);
+                Some(Statement::Return(self.child_expression(node, value)))
+            }
+            SyntaxKind::ThrowStatement => {
+                self.span(node);
+                Some(Statement::Throw(self.child_expression(node, value)))
+            }
+            SyntaxKind::ExpressionStatement => {
+                self.span(node);
+                Some(Statement::Expression(self.child_expression(node, value)))
+            }
+            // Leftovers of a statement that failed inside a block
+            _ => None,
+        }
+    }
+
+    // A missing child only happens in hand-made trees, it becomes
+    // Expression::None spanning its parent
+    fn child_expression(&mut self, parent: &SyntaxNode, child: Option<&SyntaxNode>) -> Expression {
+        match child {
+            Some(child) => self.expression(child),
+            None => {
+                self.span(parent);
+                Expression::None
+            }
+        }
+    }
+
+    fn expression(&mut self, node: &SyntaxNode) -> Expression {
+        // The AST does not keep parentheses
+        if node.kind == SyntaxKind::ParenExpression {
+            let inner = node.nodes().next();
+            return self.child_expression(node, inner);
+        }
+
+        self.span(node);
+
+        let mut nodes = node.nodes();
+        let first_token = node.tokens().next().map(|token| &token.token);
+
+        match (node.kind, first_token) {
+            (SyntaxKind::Identifier, Some(Token::Ident(name))) => {
+                Expression::Identifier(name.clone())
+            }
+            (SyntaxKind::Literal, Some(Token::Int(nb))) => Expression::Int(*nb),
+            (SyntaxKind::Literal, Some(Token::BigInt(nb))) => Expression::BigInt(nb.clone()),
+            (SyntaxKind::Literal, Some(Token::Float(nb))) => Expression::Float(*nb),
+            (SyntaxKind::Literal, Some(Token::Str(str))) => Expression::Str(str.clone()),
+            (SyntaxKind::Literal, Some(Token::True)) => Expression::Bool(true),
+            (SyntaxKind::Literal, Some(Token::False)) => Expression::Bool(false),
+            (SyntaxKind::PrefixExpression, Some(operator)) => {
+                match PrefixOperator::try_from(operator) {
+                    Ok(operator) => Expression::Prefix {
+                        operator,
+                        right: Box::new(self.child_expression(node, nodes.next())),
+                    },
+                    Err(_) => Expression::None,
+                }
+            }
+            // The operator is the only token of the node
+            (SyntaxKind::InfixExpression, Some(operator)) => Expression::Infix {
+                left: Box::new(self.child_expression(node, nodes.next())),
+                operator: InfixOperator::from(operator),
+                right: Box::new(self.child_expression(node, nodes.next())),
+            },
+            (SyntaxKind::IfExpression, _) => {
+                let condition = self.child_expression(node, nodes.next());
+                let mut blocks = nodes
+                    .filter(|node| node.kind == SyntaxKind::Block)
+                    .map(|block| self.statements(block))
+                    .collect::<Vec<_>>()
+                    .into_iter();
+                Expression::If {
+                    condition: Box::new(condition),
+                    consequence: blocks.next().unwrap_or_default(),
+                    alternative: blocks.next(),
+                }
+            }
+            (SyntaxKind::FunctionExpression, _) => {
+                let mut parameters = vec![];
+                let mut body = vec![];
+                for node in nodes {
+                    match node.kind {
+                        SyntaxKind::ParameterList => {
+                            for token in node.tokens() {
+                                if !matches!(
+                                    token.token,
+                                    Token::LParen | Token::RParen | Token::Comma
+                                ) {
+                                    self.spans.push(token.span());
+                                    parameters
+                                        .push(Expression::Identifier(token.token.to_string()));
+                                }
+                            }
+                        }
+                        SyntaxKind::Block => body = self.statements(node),
+                        _ => {}
+                    }
+                }
+                Expression::Function { parameters, body }
+            }
+            // Each block belongs to the keyword before it
+            (SyntaxKind::TryExpression, _) => {
+                let mut body = vec![];
+                let mut catch = None;
+                let mut finally = None;
+                let mut keyword = Token::Try;
+                let mut name = String::new();
+                for child in &node.children {
+                    match child {
+                        SyntaxElement::Token(token) => match &token.token {
+                            Token::Ident(ident) => name = ident.clone(),
+                            Token::Try | Token::Catch | Token::Finally => {
+                                keyword = token.token.clone()
+                            }
+                            _ => {}
+                        },
+                        SyntaxElement::Node(block) if block.kind == SyntaxKind::Block => {
+                            let statements = self.statements(block);
+                            match keyword {
+                                Token::Catch => catch = Some((name.clone(), statements)),
+                                Token::Finally => finally = Some(statements),
+                                _ => body = statements,
+                            }
+                        }
+                        SyntaxElement::Node(_) => {}
+                    }
+                }
+                Expression::Try {
+                    body,
+                    catch,
+                    finally,
+                }
+            }
+            (SyntaxKind::PropagateExpression, _) => {
+                Expression::Propagate(Box::new(self.child_expression(node, nodes.next())))
+            }
+            (SyntaxKind::CallExpression, _) => {
+                let function = self.child_expression(node, nodes.next());
+                let arguments = match nodes.find(|node| node.kind == SyntaxKind::ArgumentList) {
+                    Some(list) => list.nodes().map(|arg| self.expression(arg)).collect(),
+                    None => vec![],
+                };
+                Expression::FunctionCall {
+                    function: Box::new(function),
+                    arguments,
+                    span: Location(node.span().unwrap_or_default()),
+                }
+            }
+            _ => Expression::None,
+        }
+    }
+}
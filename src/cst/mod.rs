@@ -0,0 +1,221 @@
+#[cfg(test)]
+mod tests;
+
+mod lower;
+
+pub use lower::{lower, lower_with_spans, SourceMap};
+
+use crate::{
+    lexer::Lexer,
+    parser::{Parser, ParserError},
+    token::{Position, Span, Token},
+};
+
+// Whitespace and comments, which the AST drops
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriviaKind {
+    // Any run of whitespace other than a line feed
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+}
+
+// A token with its source text. The leading trivia is everything since the
+// previous token, the trailing trivia runs up to and including the end of
+// the line, block comments excepted
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxToken {
+    pub token: Token,
+    pub text: String,
+    // Where the text starts, trivia excluded
+    pub position: Position,
+    pub leading: Vec<Trivia>,
+    pub trailing: Vec<Trivia>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyntaxKind {
+    Program,
+    LetStatement,
+    ReturnStatement,
+    ThrowStatement,
+    ExpressionStatement,
+    Block,
+    Identifier,
+    // Integer, float, string or boolean
+    Literal,
+    PrefixExpression,
+    InfixExpression,
+    ParenExpression,
+    IfExpression,
+    FunctionExpression,
+    TryExpression,
+    ParameterList,
+    CallExpression,
+    // An expression followed by ?
+    PropagateExpression,
+    ArgumentList,
+    // Tokens of a statement that failed to parse
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+// Printing a node gives back its source text byte for byte
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxNode {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl std::fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.children
+            .iter()
+            .try_for_each(|child| write!(f, "{}", child))
+    }
+}
+
+impl std::fmt::Display for SyntaxElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyntaxElement::Node(node) => write!(f, "{}", node),
+            SyntaxElement::Token(token) => write!(f, "{}", token),
+        }
+    }
+}
+
+impl std::fmt::Display for SyntaxToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.leading
+            .iter()
+            .try_for_each(|t| write!(f, "{}", t.text))?;
+        write!(f, "{}", self.text)?;
+        self.trailing
+            .iter()
+            .try_for_each(|t| write!(f, "{}", t.text))
+    }
+}
+
+impl SyntaxToken {
+    pub fn span(&self) -> Span {
+        Span {
+            start: self.position,
+            end: self.position.advance(&self.text),
+        }
+    }
+}
+
+impl SyntaxNode {
+    pub fn nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = &SyntaxToken> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Token(token) => Some(token),
+            SyntaxElement::Node(_) => None,
+        })
+    }
+
+    // Start of the first token of the node, trivia excluded
+    pub fn position(&self) -> Option<Position> {
+        self.children.iter().find_map(|child| match child {
+            SyntaxElement::Token(token) => Some(token.position),
+            SyntaxElement::Node(node) => node.position(),
+        })
+    }
+
+    // From the first token to the end of the last one, trivia excluded
+    pub fn span(&self) -> Option<Span> {
+        let end = self.last_token()?;
+        Some(Span {
+            start: self.position()?,
+            end: end.position.advance(&end.text),
+        })
+    }
+
+    fn last_token(&self) -> Option<&SyntaxToken> {
+        self.children.iter().rev().find_map(|child| match child {
+            SyntaxElement::Token(token) => Some(token),
+            SyntaxElement::Node(node) => node.last_token(),
+        })
+    }
+
+    // Builds the tree from every token of the source and the token ranges
+    // of the nodes, given as (kind, first index, last index) and in the
+    // order the parser completed them
+    pub(crate) fn build(tokens: Vec<SyntaxToken>, ranges: Vec<(SyntaxKind, usize, usize)>) -> Self {
+        let mut ranges: Vec<_> = ranges.into_iter().enumerate().collect();
+        // Outer nodes first. Of two nodes on the same tokens, the parent is
+        // the one completed last
+        ranges.sort_by(
+            |(a_order, (_, a_start, a_end)), (b_order, (_, b_start, b_end))| {
+                a_start
+                    .cmp(b_start)
+                    .then(b_end.cmp(a_end))
+                    .then(b_order.cmp(a_order))
+            },
+        );
+        let mut ranges = ranges.into_iter().map(|(_, range)| range).peekable();
+
+        let mut stack = vec![(
+            SyntaxNode {
+                kind: SyntaxKind::Program,
+                children: vec![],
+            },
+            usize::MAX,
+        )];
+
+        for (index, token) in tokens.into_iter().enumerate() {
+            while let Some((kind, _, end)) = ranges.next_if(|(_, start, _)| *start == index) {
+                let node = SyntaxNode {
+                    kind,
+                    children: vec![],
+                };
+                stack.push((node, end));
+            }
+
+            let (parent, _) = stack.last_mut().expect("the program is never popped");
+            parent.children.push(SyntaxElement::Token(token));
+
+            while stack.len() > 1 && stack.last().is_some_and(|(_, end)| *end == index) {
+                let (node, _) = stack.pop().expect("checked above");
+                let (parent, _) = stack.last_mut().expect("the program is never popped");
+                parent.children.push(SyntaxElement::Node(node));
+            }
+        }
+
+        // Closes the nodes still open, normally only the program
+        while let Some((node, _)) = stack.pop() {
+            match stack.last_mut() {
+                Some((parent, _)) => parent.children.push(SyntaxElement::Node(node)),
+                None => return node,
+            }
+        }
+        unreachable!("the program is on the stack")
+    }
+}
+
+// Parses the source into a lossless syntax tree. The tree is built even when
+// there are errors, failed statements end up in Error nodes
+pub fn parse(input: String) -> (SyntaxNode, Vec<ParserError>) {
+    let mut parser = Parser::new_lossless(Lexer::new(input));
+    let _ = parser.parse_program();
+    let errors = std::mem::take(&mut parser.errors);
+    (parser.syntax_tree(), errors)
+}
@@ -0,0 +1,226 @@
+use super::*;
+use crate::ast::Program;
+
+fn parse_ast(input: &str) -> Program {
+    let mut parser = Parser::new(Lexer::new(input.to_string()));
+    parser.parse_program().unwrap()
+}
+
+#[test]
+fn round_trip() {
+    let tests = vec![
+        "",
+        "   \n\n",
+        "// only a comment",
+        "let x = 5;",
+        "let   x=5 ;  // five\n\n/* a block\n comment */ x",
+        "let add = fn(a, /* b */ b) {\n    a + b; // sum\n};\nadd(1,2)\n",
+        "if (x < 10) { \"small\" } else { /* /* nested */ */ \"big\" }",
+        "-a * (b + c) >> 2 | ~d\r\n",
+        "let f = fn() { return 1.5e3; }; f()(0x1F, 12345678901234567890);",
+        "try { f() } catch ( e ) { throw e; } finally { /* done */ }",
+        "let x = parse(s) ? + f()?? ;",
+        // Errors are kept too
+        "let = 5; let y = 1;\nlet z = @;",
+        "fn(x) { let = ; x }",
+        "let x = 1 /* unterminated",
+        "\"unterminated",
+    ];
+
+    for input in tests {
+        let (tree, _) = parse(input.to_string());
+        assert_eq!(tree.kind, SyntaxKind::Program);
+        assert_eq!(tree.to_string(), input);
+    }
+}
+
+#[test]
+fn lowering() {
+    let tests = vec![
+        "let x = 5; // comment\nx",
+        "let add = fn(a, b) { a + b; }; add(1, add(2, 3))",
+        "if (x < 10) { return 1 } else { 2 }",
+        "-a * (b + c) >> 2 | ~d; !true == false",
+        "let f = fn() { fn(x) { x } }; f()(\"a\" + \"b\"); 2.5; 99999999999999999999",
+        "let = 5; let y = 1;",
+        "fn(x) { let = ; x }",
+        "let x = try { f() } catch (e) { throw error(\"Wrapped\", message(e)) }; x",
+        "try { 1 } finally { return 2 }; try { throw 3; } catch (err) { err }",
+        "let f = fn(r) { -(r)? + g(r?)?? }; f(ok(1))",
+    ];
+
+    for input in tests {
+        let (tree, _) = parse(input.to_string());
+        assert_eq!(lower(&tree), parse_ast(input), "{}", input);
+    }
+}
+
+#[test]
+fn nodes() {
+    let (tree, errors) = parse("let x = a + f(1);".to_string());
+    assert!(errors.is_empty());
+
+    fn kinds(node: &SyntaxNode, out: &mut Vec<SyntaxKind>) {
+        out.push(node.kind);
+        node.nodes().for_each(|child| kinds(child, out));
+    }
+
+    let mut out = vec![];
+    kinds(&tree, &mut out);
+    assert_eq!(
+        out,
+        vec![
+            SyntaxKind::Program,
+            SyntaxKind::LetStatement,
+            SyntaxKind::InfixExpression,
+            SyntaxKind::Identifier,
+            SyntaxKind::CallExpression,
+            SyntaxKind::Identifier,
+            SyntaxKind::ArgumentList,
+            SyntaxKind::Literal,
+        ]
+    );
+}
+
+#[test]
+fn trivia() {
+    let (tree, _) = parse("// head\nlet x = 1; // one\n  /* two */ x".to_string());
+
+    let statements: Vec<_> = tree.nodes().collect();
+    let let_tokens: Vec<_> = statements[0].tokens().collect();
+
+    // The comment on its own line leads the first token
+    let leading: Vec<_> = let_tokens[0].leading.iter().map(|t| t.kind).collect();
+    assert_eq!(leading, vec![TriviaKind::LineComment, TriviaKind::Newline]);
+
+    // The comment after the semicolon trails it, up to the end of the line
+    let semicolon = let_tokens.last().unwrap();
+    assert_eq!(semicolon.text, ";");
+    let trailing: Vec<_> = semicolon.trailing.iter().map(|t| t.text.as_str()).collect();
+    assert_eq!(trailing, vec![" ", "// one", "\n"]);
+
+    // Block comments always go to the following token
+    let x = statements[1].position().unwrap();
+    assert_eq!((x.line, x.column), (3, 13));
+    let x_token = statements[1]
+        .nodes()
+        .next()
+        .unwrap()
+        .tokens()
+        .next()
+        .unwrap();
+    let leading: Vec<_> = x_token.leading.iter().map(|t| t.kind).collect();
+    assert_eq!(
+        leading,
+        vec![
+            TriviaKind::Whitespace,
+            TriviaKind::BlockComment,
+            TriviaKind::Whitespace
+        ]
+    );
+}
+
+#[test]
+fn error_nodes() {
+    let (tree, errors) = parse("1 +;\nlet y = 1;".to_string());
+    assert_eq!(errors.len(), 1);
+
+    let kinds: Vec<_> = tree.nodes().map(|node| node.kind).collect();
+    assert_eq!(kinds, vec![SyntaxKind::Error, SyntaxKind::LetStatement]);
+    assert_eq!(tree.nodes().next().unwrap().to_string(), "1 +;\n");
+
+    // Eof stays at the program level, with the trailing trivia of the source
+    let eof = tree.tokens().last().unwrap();
+    assert_eq!(eof.token, Token::Eof);
+}
+
+#[test]
+fn source_map() {
+    use crate::ast::{visitor, Expression, Statement, Visitor};
+
+    // Collects the nodes the way the source map orders them
+    #[derive(Default)]
+    struct Nodes(Vec<String>);
+
+    impl Visitor for Nodes {
+        fn visit_statement(&mut self, statement: &Statement) {
+            self.0.push(statement.to_string());
+            visitor::walk_statement(self, statement);
+        }
+
+        fn visit_expression(&mut self, expression: &Expression) {
+            self.0.push(expression.to_string());
+            visitor::walk_expression(self, expression);
+        }
+    }
+
+    let input = "let f = fn(a, b) { (a + b) * 2 }; if (f(1, 2) > 5) { \"big\" } else { -1 }";
+    let (tree, _) = parse(input.to_string());
+    let (program, source_map) = lower_with_spans(&tree);
+
+    let mut nodes = Nodes::default();
+    nodes.visit_program(&program);
+
+    let sources: Vec<_> = source_map
+        .spans
+        .iter()
+        .map(|span| &input[span.start.offset..span.end.offset])
+        .collect();
+    assert_eq!(sources.len(), nodes.0.len());
+    assert_eq!(
+        sources[..8],
+        [
+            "let f = fn(a, b) { (a + b) * 2 };",
+            "fn(a, b) { (a + b) * 2 }",
+            "a",
+            "b",
+            "(a + b) * 2",
+            "(a + b) * 2",
+            "a + b",
+            "a",
+        ]
+    );
+}
+
+// AST equality ignores locations, the spans of calls are compared here
+#[test]
+fn call_spans() {
+    use crate::ast::{visitor, Expression, Visitor};
+
+    #[derive(Default)]
+    struct Calls(Vec<Span>);
+
+    impl Visitor for Calls {
+        fn visit_expression(&mut self, expression: &Expression) {
+            if let Expression::FunctionCall { span, .. } = expression {
+                self.0.push(span.0);
+            }
+            visitor::walk_expression(self, expression);
+        }
+    }
+
+    let input = "f(1)(g( 2 ), (h))(3);\n  (fn(x) { x })(\n  4\n) /* c */ ;";
+    let (tree, _) = parse(input.to_string());
+    let mut lowered = Calls::default();
+    lowered.visit_program(&lower(&tree));
+
+    let mut parsed = Calls::default();
+    parsed.visit_program(&parse_ast(input));
+
+    let sources: Vec<_> = parsed
+        .0
+        .iter()
+        .map(|span| &input[span.start.offset..span.end.offset])
+        .collect();
+    assert_eq!(
+        sources,
+        vec![
+            "f(1)(g( 2 ), (h))(3)",
+            "f(1)(g( 2 ), (h))",
+            "f(1)",
+            "g( 2 )",
+            "(fn(x) { x })(\n  4\n)"
+        ]
+    );
+    assert_eq!(parsed.0, lowered.0);
+}
@@ -0,0 +1,172 @@
+use crate::ast::{escape_string, Expression, Program, Statement};
+
+// Graphviz digraph with one node per statement and expression. Edges are
+// labeled with the field they stand for, and children keep their order
+pub fn to_dot(program: &Program) -> String {
+    let mut emitter = DotEmitter {
+        out: String::from("digraph ast {\n  ordering=out;\n  node [shape=box];\n"),
+        next_id: 0,
+    };
+
+    let root = emitter.node("Program");
+    for statement in &program.statements {
+        let child = emitter.statement(statement);
+        emitter.edge(root, child, "statement");
+    }
+
+    emitter.out.push_str("}\n");
+    emitter.out
+}
+
+struct DotEmitter {
+    out: String,
+    next_id: usize,
+}
+
+impl DotEmitter {
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.out
+            .push_str(&format!("  n{} [label=\"{}\"];\n", id, escape_label(label)));
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: &str) {
+        self.out
+            .push_str(&format!("  n{} -> n{} [label=\"{}\"];\n", from, to, label));
+    }
+
+    fn statements(&mut self, parent: usize, statements: &[Statement], label: &str) {
+        for statement in statements {
+            let child = self.statement(statement);
+            self.edge(parent, child, label);
+        }
+    }
+
+    fn expressions(&mut self, parent: usize, expressions: &[Expression], label: &str) {
+        for expression in expressions {
+            let child = self.expression(expression);
+            self.edge(parent, child, label);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) -> usize {
+        match statement {
+            Statement::Let { name, value } => {
+                let id = self.node(&format!("Let {}", name));
+                self.expressions(id, std::slice::from_ref(value), "value");
+                id
+            }
+            Statement::Return(value) => {
+                let id = self.node("Return");
+                self.expressions(id, std::slice::from_ref(value), "value");
+                id
+            }
+            Statement::Throw(value) => {
+                let id = self.node("Throw");
+                self.expressions(id, std::slice::from_ref(value), "value");
+                id
+            }
+            Statement::Expression(value) => {
+                let id = self.node("ExpressionStatement");
+                self.expressions(id, std::slice::from_ref(value), "expression");
+                id
+            }
+            Statement::Block(statements) => {
+                let id = self.node("Block");
+                self.statements(id, statements, "statement");
+                id
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) -> usize {
+        match expression {
+            Expression::None => self.node("None"),
+            Expression::Identifier(name) => self.node(&format!("Identifier {}", name)),
+            Expression::Int(value) => self.node(&format!("Integer {}", value)),
+            Expression::BigInt(value) => self.node(&format!("Integer {}", value)),
+            Expression::Float(value) => self.node(&format!("Float {:?}", value)),
+            Expression::Str(value) => self.node(&format!("String \"{}\"", escape_string(value))),
+            Expression::Bool(value) => self.node(&format!("Boolean {}", value)),
+            Expression::Prefix { operator, right } => {
+                let id = self.node(&format!("Prefix {}", operator));
+                let right = self.expression(right);
+                self.edge(id, right, "right");
+                id
+            }
+            Expression::Infix {
+                left,
+                operator,
+                right,
+            } => {
+                let id = self.node(&format!("Infix {}", operator));
+                let left = self.expression(left);
+                self.edge(id, left, "left");
+                let right = self.expression(right);
+                self.edge(id, right, "right");
+                id
+            }
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                let id = self.node("If");
+                let condition = self.expression(condition);
+                self.edge(id, condition, "condition");
+                self.statements(id, consequence, "consequence");
+                if let Some(alternative) = alternative {
+                    self.statements(id, alternative, "alternative");
+                }
+                id
+            }
+            Expression::Function { parameters, body } => {
+                let id = self.node("Function");
+                self.expressions(id, parameters, "parameter");
+                self.statements(id, body, "body");
+                id
+            }
+            Expression::Propagate(value) => {
+                let id = self.node("Propagate");
+                let value = self.expression(value);
+                self.edge(id, value, "value");
+                id
+            }
+            Expression::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                let id = match catch {
+                    Some((name, _)) => self.node(&format!("Try catch {}", name)),
+                    None => self.node("Try"),
+                };
+                self.statements(id, body, "body");
+                if let Some((_, handler)) = catch {
+                    self.statements(id, handler, "catch");
+                }
+                if let Some(finally) = finally {
+                    self.statements(id, finally, "finally");
+                }
+                id
+            }
+            Expression::FunctionCall {
+                function,
+                arguments,
+                ..
+            } => {
+                let id = self.node("Call");
+                let function = self.expression(function);
+                self.edge(id, function, "function");
+                self.expressions(id, arguments, "argument");
+                id
+            }
+        }
+    }
+}
+
+fn escape_label(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,271 @@
+use super::Spans;
+use crate::{
+    ast::{Expression, Program, Statement},
+    cst::SourceMap,
+    token::{Position, Span},
+};
+
+// The schema is documented in the README, under "AST dumps"
+pub fn to_json(program: &Program, source_map: Option<&SourceMap>) -> String {
+    let mut emitter = JsonEmitter {
+        spans: Spans::new(source_map),
+    };
+    let json = Json::Object(vec![
+        ("kind", Json::str("Program")),
+        ("statements", emitter.statements(&program.statements)),
+    ]);
+
+    let mut out = String::new();
+    json.write(&mut out, 0);
+    out.push('\n');
+    out
+}
+
+enum Json {
+    Null,
+    Bool(bool),
+    // Already formatted
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn str(str: &str) -> Self {
+        Json::String(str.to_string())
+    }
+
+    // Pretty printed with 2 spaces of indentation, empty arrays and objects
+    // stay on one line
+    fn write(&self, out: &mut String, indent: usize) {
+        let pad = |out: &mut String, indent: usize| out.push_str(&"  ".repeat(indent));
+
+        match self {
+            Json::Null => out.push_str("null"),
+            Json::Bool(value) => out.push_str(&value.to_string()),
+            Json::Number(value) => out.push_str(value),
+            Json::String(value) => write_string(out, value),
+            Json::Array(items) if items.is_empty() => out.push_str("[]"),
+            Json::Array(items) => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    pad(out, indent + 1);
+                    item.write(out, indent + 1);
+                    out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                pad(out, indent);
+                out.push(']');
+            }
+            Json::Object(fields) if fields.is_empty() => out.push_str("{}"),
+            Json::Object(fields) => {
+                out.push_str("{\n");
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    pad(out, indent + 1);
+                    write_string(out, key);
+                    out.push_str(": ");
+                    value.write(out, indent + 1);
+                    out.push_str(if i + 1 < fields.len() { ",\n" } else { "\n" });
+                }
+                pad(out, indent);
+                out.push('}');
+            }
+        }
+    }
+}
+
+fn write_string(out: &mut String, str: &str) {
+    out.push('"');
+    for ch in str.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if ch.is_control() => out.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+}
+
+struct JsonEmitter<'a> {
+    spans: Spans<'a>,
+}
+
+impl JsonEmitter<'_> {
+    // The kind first, then the span when known, then the fields of the node
+    fn node(
+        &mut self,
+        kind: &'static str,
+        span: Option<Span>,
+        fields: Vec<(&'static str, Json)>,
+    ) -> Json {
+        let mut object = vec![("kind", Json::str(kind))];
+        if let Some(span) = span {
+            object.push(("span", span_json(span)));
+        }
+        object.extend(fields);
+        Json::Object(object)
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> Json {
+        Json::Array(statements.iter().map(|s| self.statement(s)).collect())
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Json {
+        let span = self.spans.next();
+
+        match statement {
+            Statement::Let { name, value } => {
+                let fields = vec![("name", Json::str(name)), ("value", self.expression(value))];
+                self.node("Let", span, fields)
+            }
+            Statement::Return(value) => {
+                let fields = vec![("value", self.expression(value))];
+                self.node("Return", span, fields)
+            }
+            Statement::Throw(value) => {
+                let fields = vec![("value", self.expression(value))];
+                self.node("Throw", span, fields)
+            }
+            Statement::Expression(value) => {
+                let fields = vec![("expression", self.expression(value))];
+                self.node("ExpressionStatement", span, fields)
+            }
+            Statement::Block(statements) => {
+                let fields = vec![("statements", self.statements(statements))];
+                self.node("Block", span, fields)
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Json {
+        let span = self.spans.next();
+
+        let (kind, fields) = match expression {
+            Expression::None => ("None", vec![]),
+            Expression::Identifier(name) => ("Identifier", vec![("name", Json::str(name))]),
+            // As strings, integers may not fit in a double
+            Expression::Int(value) => ("Integer", vec![("value", Json::String(value.to_string()))]),
+            Expression::BigInt(value) => {
+                ("Integer", vec![("value", Json::String(value.to_string()))])
+            }
+            // NaN and infinities have no JSON representation
+            Expression::Float(value) if value.is_finite() => (
+                "Float",
+                vec![("value", Json::Number(format!("{:?}", value)))],
+            ),
+            Expression::Float(_) => ("Float", vec![("value", Json::Null)]),
+            Expression::Str(value) => ("String", vec![("value", Json::str(value))]),
+            Expression::Bool(value) => ("Boolean", vec![("value", Json::Bool(*value))]),
+            Expression::Prefix { operator, right } => (
+                "Prefix",
+                vec![
+                    ("operator", Json::String(operator.to_string())),
+                    ("right", self.expression(right)),
+                ],
+            ),
+            Expression::Infix {
+                left,
+                operator,
+                right,
+            } => (
+                "Infix",
+                vec![
+                    ("operator", Json::String(operator.to_string())),
+                    ("left", self.expression(left)),
+                    ("right", self.expression(right)),
+                ],
+            ),
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => (
+                "If",
+                vec![
+                    ("condition", self.expression(condition)),
+                    ("consequence", self.statements(consequence)),
+                    (
+                        "alternative",
+                        match alternative {
+                            Some(alternative) => self.statements(alternative),
+                            None => Json::Null,
+                        },
+                    ),
+                ],
+            ),
+            Expression::Function { parameters, body } => (
+                "Function",
+                vec![
+                    (
+                        "parameters",
+                        Json::Array(parameters.iter().map(|p| self.expression(p)).collect()),
+                    ),
+                    ("body", self.statements(body)),
+                ],
+            ),
+            Expression::Propagate(value) => ("Propagate", vec![("value", self.expression(value))]),
+            Expression::Try {
+                body,
+                catch,
+                finally,
+            } => (
+                "Try",
+                vec![
+                    ("body", self.statements(body)),
+                    (
+                        "catch",
+                        match catch {
+                            Some((name, handler)) => Json::Object(vec![
+                                ("name", Json::str(name)),
+                                ("body", self.statements(handler)),
+                            ]),
+                            None => Json::Null,
+                        },
+                    ),
+                    (
+                        "finally",
+                        match finally {
+                            Some(finally) => self.statements(finally),
+                            None => Json::Null,
+                        },
+                    ),
+                ],
+            ),
+            Expression::FunctionCall {
+                function,
+                arguments,
+                ..
+            } => (
+                "Call",
+                vec![
+                    ("function", self.expression(function)),
+                    (
+                        "arguments",
+                        Json::Array(arguments.iter().map(|a| self.expression(a)).collect()),
+                    ),
+                ],
+            ),
+        };
+
+        self.node(kind, span, fields)
+    }
+}
+
+fn span_json(span: Span) -> Json {
+    let position = |position: Position| {
+        Json::Object(vec![
+            ("offset", Json::Number(position.offset.to_string())),
+            ("line", Json::Number(position.line.to_string())),
+            ("column", Json::Number(position.column.to_string())),
+        ])
+    };
+
+    Json::Object(vec![
+        ("start", position(span.start)),
+        ("end", position(span.end)),
+    ])
+}
@@ -0,0 +1,81 @@
+#[cfg(test)]
+mod tests;
+
+mod dot;
+mod json;
+mod sexpr;
+
+pub use dot::to_dot;
+pub use json::to_json;
+pub use sexpr::to_sexpr;
+
+use crate::{
+    cst::{self, SourceMap},
+    parser::ParserError,
+    token::Span,
+};
+use std::str::FromStr;
+
+// Output formats of --emit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Emit {
+    AstJson,
+    Sexpr,
+    // Graphviz, without spans
+    Dot,
+}
+
+impl std::fmt::Display for Emit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Emit::AstJson => write!(f, "ast-json"),
+            Emit::Sexpr => write!(f, "sexpr"),
+            Emit::Dot => write!(f, "dot"),
+        }
+    }
+}
+
+impl FromStr for Emit {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ast-json" => Ok(Self::AstJson),
+            "sexpr" => Ok(Self::Sexpr),
+            "dot" => Ok(Self::Dot),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Emit {
+    // Parses the source and dumps its AST, with the spans of the nodes where
+    // the format has them
+    pub fn source(&self, input: String) -> Result<String, Vec<ParserError>> {
+        let (tree, errors) = cst::parse(input);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let (program, source_map) = cst::lower_with_spans(&tree);
+        Ok(match self {
+            Emit::AstJson => to_json(&program, Some(&source_map)),
+            Emit::Sexpr => to_sexpr(&program, Some(&source_map)),
+            Emit::Dot => to_dot(&program),
+        })
+    }
+}
+
+// Hands out the spans of a source map in the order the nodes are visited,
+// nothing without a source map
+struct Spans<'a>(Option<std::slice::Iter<'a, Span>>);
+
+impl<'a> Spans<'a> {
+    fn new(source_map: Option<&'a SourceMap>) -> Self {
+        Spans(source_map.map(|map| map.spans.iter()))
+    }
+
+    fn next(&mut self) -> Option<Span> {
+        self.0.as_mut()?.next().copied()
+    }
+}
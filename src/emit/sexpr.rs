@@ -0,0 +1,141 @@
+use super::Spans;
+use crate::{
+    ast::{escape_string, Expression, Program, Statement},
+    cst::SourceMap,
+    token::Span,
+};
+
+// The schema is documented in the README, under "AST dumps". Each statement
+// of the program goes on its own line
+pub fn to_sexpr(program: &Program, source_map: Option<&SourceMap>) -> String {
+    let mut emitter = SexprEmitter {
+        spans: Spans::new(source_map),
+    };
+
+    let mut out = String::from("(program");
+    for statement in &program.statements {
+        out.push_str("\n  ");
+        out.push_str(&emitter.statement(statement));
+    }
+    out.push_str(")\n");
+    out
+}
+
+struct SexprEmitter<'a> {
+    spans: Spans<'a>,
+}
+
+impl SexprEmitter<'_> {
+    // (name items...), also for lists that are not nodes
+    fn list(name: &str, items: Vec<String>) -> String {
+        let mut out = format!("({}", name);
+        for item in items {
+            out.push(' ');
+            out.push_str(&item);
+        }
+        out.push(')');
+        out
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> Vec<String> {
+        statements.iter().map(|s| self.statement(s)).collect()
+    }
+
+    // The span is taken before the children, in visiting order
+    fn statement(&mut self, statement: &Statement) -> String {
+        let span = self.spans.next();
+        let (kind, items) = match statement {
+            Statement::Let { name, value } => ("let", vec![name.clone(), self.expression(value)]),
+            Statement::Return(value) => ("return", vec![self.expression(value)]),
+            Statement::Throw(value) => ("throw", vec![self.expression(value)]),
+            Statement::Expression(value) => ("expr", vec![self.expression(value)]),
+            Statement::Block(statements) => ("block", self.statements(statements)),
+        };
+        self.with_span(kind, span, items)
+    }
+
+    fn expression(&mut self, expression: &Expression) -> String {
+        let span = self.spans.next();
+        let (kind, items) = match expression {
+            Expression::None => ("none", vec![]),
+            Expression::Identifier(name) => ("ident", vec![name.clone()]),
+            Expression::Int(value) => ("int", vec![value.to_string()]),
+            Expression::BigInt(value) => ("int", vec![value.to_string()]),
+            Expression::Float(value) => ("float", vec![format!("{:?}", value)]),
+            Expression::Str(value) => ("string", vec![format!("\"{}\"", escape_string(value))]),
+            Expression::Bool(value) => ("bool", vec![value.to_string()]),
+            Expression::Prefix { operator, right } => {
+                ("prefix", vec![operator.to_string(), self.expression(right)])
+            }
+            Expression::Infix {
+                left,
+                operator,
+                right,
+            } => (
+                "infix",
+                vec![
+                    operator.to_string(),
+                    self.expression(left),
+                    self.expression(right),
+                ],
+            ),
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                let mut items = vec![
+                    self.expression(condition),
+                    Self::list("then", self.statements(consequence)),
+                ];
+                if let Some(alternative) = alternative {
+                    items.push(Self::list("else", self.statements(alternative)));
+                }
+                ("if", items)
+            }
+            Expression::Function { parameters, body } => {
+                let parameters = parameters.iter().map(|p| self.expression(p)).collect();
+                let body = self.statements(body);
+                (
+                    "fn",
+                    vec![Self::list("params", parameters), Self::list("body", body)],
+                )
+            }
+            Expression::Propagate(value) => ("propagate", vec![self.expression(value)]),
+            Expression::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                let mut items = vec![Self::list("body", self.statements(body))];
+                if let Some((name, handler)) = catch {
+                    let handler = [vec![name.clone()], self.statements(handler)].concat();
+                    items.push(Self::list("catch", handler));
+                }
+                if let Some(finally) = finally {
+                    items.push(Self::list("finally", self.statements(finally)));
+                }
+                ("try", items)
+            }
+            Expression::FunctionCall {
+                function,
+                arguments,
+                ..
+            } => {
+                let function = self.expression(function);
+                let arguments = arguments.iter().map(|a| self.expression(a)).collect();
+                ("call", vec![function, Self::list("args", arguments)])
+            }
+        };
+        self.with_span(kind, span, items)
+    }
+
+    // (kind span? items...)
+    fn with_span(&self, kind: &str, span: Option<Span>, items: Vec<String>) -> String {
+        let mut head = vec![];
+        if let Some(span) = span {
+            head.push(span.to_string());
+        }
+        Self::list(kind, head.into_iter().chain(items).collect())
+    }
+}
@@ -0,0 +1,227 @@
+use super::*;
+use crate::{lexer::Lexer, parser::Parser};
+
+#[test]
+fn sexpr() {
+    let tests = vec![
+        ("", "(program)\n"),
+        (
+            "let x = 5;",
+            "(program\n  (let 1:1-1:11 x (int 1:9-1:10 5)))\n",
+        ),
+        (
+            "-(a + 1) * f(\"s\\n\", 2.5)",
+            "(program\n  (expr 1:1-1:25 (infix 1:1-1:25 * (prefix 1:1-1:9 - (infix 1:3-1:8 + (ident 1:3-1:4 a) (int 1:7-1:8 1))) (call 1:12-1:25 (ident 1:12-1:13 f) (args (string 1:14-1:19 \"s\\n\") (float 1:21-1:24 2.5))))))\n",
+        ),
+        (
+            "if (x) {\n  return true\n} else { fn(a) { a } }",
+            "(program\n  (expr 1:1-3:23 (if 1:1-3:23 (ident 1:5-1:6 x) (then (return 2:3-2:14 (bool 2:10-2:14 true))) (else (expr 3:10-3:21 (fn 3:10-3:21 (params (ident 3:13-3:14 a)) (body (expr 3:18-3:19 (ident 3:18-3:19 a)))))))))\n",
+        ),
+        (
+            "try { f() } catch (e) {\n  throw e\n} finally { 1 }",
+            "(program\n  (expr 1:1-3:16 (try 1:1-3:16 (body (expr 1:7-1:10 (call 1:7-1:10 (ident 1:7-1:8 f) (args)))) (catch e (throw 2:3-2:10 (ident 2:9-2:10 e))) (finally (expr 3:13-3:14 (int 3:13-3:14 1))))))\n",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(Emit::Sexpr.source(input.to_string()).unwrap(), expected);
+    }
+}
+
+#[test]
+fn json() {
+    let json = Emit::AstJson
+        .source("let s = \"a\\\"b\"; f(1)".to_string())
+        .unwrap();
+    let expected = r#"{
+  "kind": "Program",
+  "statements": [
+    {
+      "kind": "Let",
+      "span": {
+        "start": {
+          "offset": 0,
+          "line": 1,
+          "column": 1
+        },
+        "end": {
+          "offset": 15,
+          "line": 1,
+          "column": 16
+        }
+      },
+      "name": "s",
+      "value": {
+        "kind": "String",
+        "span": {
+          "start": {
+            "offset": 8,
+            "line": 1,
+            "column": 9
+          },
+          "end": {
+            "offset": 14,
+            "line": 1,
+            "column": 15
+          }
+        },
+        "value": "a\"b"
+      }
+    },
+    {
+      "kind": "ExpressionStatement",
+      "span": {
+        "start": {
+          "offset": 16,
+          "line": 1,
+          "column": 17
+        },
+        "end": {
+          "offset": 20,
+          "line": 1,
+          "column": 21
+        }
+      },
+      "expression": {
+        "kind": "Call",
+        "span": {
+          "start": {
+            "offset": 16,
+            "line": 1,
+            "column": 17
+          },
+          "end": {
+            "offset": 20,
+            "line": 1,
+            "column": 21
+          }
+        },
+        "function": {
+          "kind": "Identifier",
+          "span": {
+            "start": {
+              "offset": 16,
+              "line": 1,
+              "column": 17
+            },
+            "end": {
+              "offset": 17,
+              "line": 1,
+              "column": 18
+            }
+          },
+          "name": "f"
+        },
+        "arguments": [
+          {
+            "kind": "Integer",
+            "span": {
+              "start": {
+                "offset": 18,
+                "line": 1,
+                "column": 19
+              },
+              "end": {
+                "offset": 19,
+                "line": 1,
+                "column": 20
+              }
+            },
+            "value": "1"
+          }
+        ]
+      }
+    }
+  ]
+}
+"#;
+    assert_eq!(json, expected);
+}
+
+#[test]
+fn without_spans() {
+    let mut parser = Parser::new(Lexer::new("if (a) { 1 } else { b }".to_string()));
+    let program = parser.parse_program().unwrap();
+
+    assert_eq!(
+        to_sexpr(&program, None),
+        "(program\n  (expr (if (ident a) (then (expr (int 1))) (else (expr (ident b))))))\n"
+    );
+
+    let json = to_json(&program, None);
+    assert!(!json.contains("span"));
+    assert!(json.contains("\"alternative\": [\n"));
+}
+
+#[test]
+fn try_without_catch() {
+    let mut parser = Parser::new(Lexer::new("try { a } finally { b }".to_string()));
+    let program = parser.parse_program().unwrap();
+
+    assert_eq!(
+        to_sexpr(&program, None),
+        "(program\n  (expr (try (body (expr (ident a))) (finally (expr (ident b))))))\n"
+    );
+
+    let json = to_json(&program, None);
+    assert!(json.contains("\"catch\": null"));
+    assert!(json.contains("\"finally\": [\n"));
+
+    let dot = to_dot(&program);
+    assert!(dot.contains("[label=\"Try\"]"));
+    assert!(dot.contains("[label=\"finally\"]"));
+}
+
+#[test]
+fn propagate() {
+    assert_eq!(
+        Emit::Sexpr.source("f(x)?".to_string()).unwrap(),
+        "(program\n  (expr 1:1-1:6 (propagate 1:1-1:6 (call 1:1-1:5 (ident 1:1-1:2 f) (args (ident 1:3-1:4 x))))))\n"
+    );
+
+    let json = Emit::AstJson.source("r?".to_string()).unwrap();
+    assert!(json.contains("\"kind\": \"Propagate\""));
+
+    let dot = Emit::Dot.source("r?".to_string()).unwrap();
+    assert!(dot.contains(
+        "n2 [label=\"Propagate\"];\n  n3 [label=\"Identifier r\"];\n  n2 -> n3 [label=\"value\"];"
+    ));
+}
+
+#[test]
+fn errors() {
+    assert!(Emit::AstJson.source("let = 1;".to_string()).is_err());
+    assert!(Emit::Sexpr.source("f(".to_string()).is_err());
+}
+
+#[test]
+fn dot() {
+    let dot = Emit::Dot
+        .source("if (a < 1) { f(\"x\") }".to_string())
+        .unwrap();
+    let expected = r#"digraph ast {
+  ordering=out;
+  node [shape=box];
+  n0 [label="Program"];
+  n1 [label="ExpressionStatement"];
+  n2 [label="If"];
+  n3 [label="Infix <"];
+  n4 [label="Identifier a"];
+  n3 -> n4 [label="left"];
+  n5 [label="Integer 1"];
+  n3 -> n5 [label="right"];
+  n2 -> n3 [label="condition"];
+  n6 [label="ExpressionStatement"];
+  n7 [label="Call"];
+  n8 [label="Identifier f"];
+  n7 -> n8 [label="function"];
+  n9 [label="String \"x\""];
+  n7 -> n9 [label="argument"];
+  n6 -> n7 [label="expression"];
+  n2 -> n6 [label="consequence"];
+  n1 -> n2 [label="expression"];
+  n0 -> n1 [label="statement"];
+}
+"#;
+    assert_eq!(dot, expected);
+}
@@ -0,0 +1,307 @@
+use super::{
+    allocate_integer, big_integer, integer_too_large,
+    limits::{self, Meter},
+    throw, to_big_integer,
+    trace::Frame,
+    EvalError, MAX_INTEGER_BITS,
+};
+use crate::object::Object;
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive};
+use std::{rc::Rc, str::FromStr};
+
+// Functions provided by the interpreter, looked up when an identifier is not
+// bound in the environment.
+// Floating point results follow IEEE 754: sqrt(-1) is NaN, pow(0.0, -1) is inf,
+// and NaN/inf are passed through floor, ceil, round and abs unchanged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Builtin {
+    Sqrt,
+    Floor,
+    Ceil,
+    Round,
+    Abs,
+    Pow,
+    // error(kind, message) makes an error object to throw, kind(e),
+    // message(e) and stack(e) read the fields of one
+    Error,
+    Kind,
+    Message,
+    Stack,
+    // ok(value) and err(value) make results, read by the others
+    MakeOk,
+    MakeErr,
+    IsOk,
+    IsErr,
+    Unwrap,
+    UnwrapOr,
+    UnwrapErr,
+}
+
+impl std::fmt::Display for Builtin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Builtin::Sqrt => write!(f, "sqrt"),
+            Builtin::Floor => write!(f, "floor"),
+            Builtin::Ceil => write!(f, "ceil"),
+            Builtin::Round => write!(f, "round"),
+            Builtin::Abs => write!(f, "abs"),
+            Builtin::Pow => write!(f, "pow"),
+            Builtin::Error => write!(f, "error"),
+            Builtin::Kind => write!(f, "kind"),
+            Builtin::Message => write!(f, "message"),
+            Builtin::Stack => write!(f, "stack"),
+            Builtin::MakeOk => write!(f, "ok"),
+            Builtin::MakeErr => write!(f, "err"),
+            Builtin::IsOk => write!(f, "is_ok"),
+            Builtin::IsErr => write!(f, "is_err"),
+            Builtin::Unwrap => write!(f, "unwrap"),
+            Builtin::UnwrapOr => write!(f, "unwrap_or"),
+            Builtin::UnwrapErr => write!(f, "unwrap_err"),
+        }
+    }
+}
+
+impl FromStr for Builtin {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sqrt" => Ok(Self::Sqrt),
+            "floor" => Ok(Self::Floor),
+            "ceil" => Ok(Self::Ceil),
+            "round" => Ok(Self::Round),
+            "abs" => Ok(Self::Abs),
+            "pow" => Ok(Self::Pow),
+            "error" => Ok(Self::Error),
+            "kind" => Ok(Self::Kind),
+            "message" => Ok(Self::Message),
+            "stack" => Ok(Self::Stack),
+            "ok" => Ok(Self::MakeOk),
+            "err" => Ok(Self::MakeErr),
+            "is_ok" => Ok(Self::IsOk),
+            "is_err" => Ok(Self::IsErr),
+            "unwrap" => Ok(Self::Unwrap),
+            "unwrap_or" => Ok(Self::UnwrapOr),
+            "unwrap_err" => Ok(Self::UnwrapErr),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Builtin {
+    fn arity(&self) -> usize {
+        match self {
+            Builtin::Pow | Builtin::Error | Builtin::UnwrapOr => 2,
+            _ => 1,
+        }
+    }
+
+    pub(crate) fn call(&self, args: Vec<Object>) -> Result<Object, EvalError> {
+        if args.len() != self.arity() {
+            return Err(EvalError::InvalidNumberArguments {
+                expected: self.arity() as u64,
+                got: args.len() as u64,
+            });
+        }
+
+        let mut args = args.into_iter();
+        let arg = args.next().expect("arity checked above");
+
+        match self {
+            Builtin::Sqrt => Ok(Object::Float(to_float(arg)?.sqrt())),
+            // Rounding an integer is a no-op, rounding a float keeps it a float
+            Builtin::Floor => round_with(arg, f64::floor),
+            Builtin::Ceil => round_with(arg, f64::ceil),
+            Builtin::Round => round_with(arg, f64::round),
+            Builtin::Abs => match arg {
+                Object::Integer(i) => Ok(match i.checked_abs() {
+                    Some(i) => Object::Integer(i),
+                    None => Object::from(BigInt::from(i).abs()),
+                }),
+                Object::BigInt(i) => {
+                    allocate_integer(Meter::Program, i.bits())?;
+                    Ok(Object::from(i.abs()))
+                }
+                Object::Float(x) => Ok(Object::Float(x.abs())),
+                _ => Err(expected_number(arg)),
+            },
+            Builtin::Pow => {
+                let exponent = args.next().expect("arity checked above");
+                pow(arg, exponent)
+            }
+            // The stack is set when the error is thrown
+            Builtin::Error => match (arg, args.next().expect("arity checked above")) {
+                (Object::Str(kind), Object::Str(message)) => {
+                    limits::allocate(std::mem::size_of::<Object>())?;
+                    Ok(Object::Error {
+                        kind,
+                        message,
+                        stack: vec![],
+                    })
+                }
+                (Object::Str(_), got) | (got, _) => Err(EvalError::MismatchedObject {
+                    expected: "STRING".to_string(),
+                    got,
+                }),
+            },
+            // Shared with the error, only the stack makes a new string
+            Builtin::Kind => error_field(arg, |kind, _, _| Ok(kind)),
+            Builtin::Message => error_field(arg, |_, message, _| Ok(message)),
+            // One frame per line, outermost first
+            Builtin::Stack => error_field(arg, |_, _, stack| {
+                let mut lines = Vec::with_capacity(stack.len());
+                for frame in stack {
+                    let line = frame.to_string();
+                    limits::allocate(line.len())?;
+                    lines.push(line);
+                }
+                limits::allocate(lines.iter().map(|line| line.len() + 1).sum())?;
+                Ok(lines.join("\n").into())
+            }),
+            Builtin::MakeOk => Ok(Object::Ok {
+                value: result_value(arg)?,
+            }),
+            Builtin::MakeErr => Ok(Object::Err {
+                value: result_value(arg)?,
+            }),
+            Builtin::IsOk => Ok(Object::Bool(result(arg)?.is_ok())),
+            Builtin::IsErr => Ok(Object::Bool(result(arg)?.is_err())),
+            // The value of an err is thrown, so that a catch clause gets it
+            Builtin::Unwrap => match result(arg)? {
+                Ok(value) => Ok(value),
+                Err(value) => Err(EvalError::Thrown(Box::new(throw(value)))),
+            },
+            Builtin::UnwrapOr => {
+                let default = args.next().expect("arity checked above");
+                Ok(result(arg)?.unwrap_or(default))
+            }
+            Builtin::UnwrapErr => match result(arg)? {
+                Ok(value) => Err(EvalError::Custom(format!(
+                    "unwrap_err called on ok({})",
+                    value
+                ))),
+                Err(value) => Ok(value),
+            },
+        }
+    }
+}
+
+// A new string is counted as allocated by the field, like the strings made by
+// the program
+#[allow(clippy::type_complexity)]
+fn error_field(
+    arg: Object,
+    field: fn(Rc<str>, Rc<str>, Vec<Frame>) -> Result<Rc<str>, EvalError>,
+) -> Result<Object, EvalError> {
+    match arg {
+        Object::Error {
+            kind,
+            message,
+            stack,
+        } => Ok(Object::Str(field(kind, message, stack)?)),
+        _ => Err(EvalError::MismatchedObject {
+            expected: "ERROR".to_string(),
+            got: arg,
+        }),
+    }
+}
+
+// Results in results are dropped, cloned and printed recursively, so they
+// nest at most this deep rather than overflowing the stack
+const MAX_RESULT_DEPTH: usize = 100;
+
+// Counts the box of a new result as allocated
+fn result_value(value: Object) -> Result<Box<Object>, EvalError> {
+    let mut depth = 1;
+    let mut inner = &value;
+    while let Object::Ok { value } | Object::Err { value } = inner {
+        depth += 1;
+        inner = value;
+    }
+    if depth > MAX_RESULT_DEPTH {
+        return Err(EvalError::ResultTooDeep {
+            depth: MAX_RESULT_DEPTH,
+        });
+    }
+
+    limits::allocate(std::mem::size_of::<Object>())?;
+    Ok(Box::new(value))
+}
+
+fn result(arg: Object) -> Result<Result<Object, Object>, EvalError> {
+    match arg {
+        Object::Ok { value } => Ok(Ok(*value)),
+        Object::Err { value } => Ok(Err(*value)),
+        _ => Err(EvalError::MismatchedObject {
+            expected: "RESULT".to_string(),
+            got: arg,
+        }),
+    }
+}
+
+fn round_with(arg: Object, round: fn(f64) -> f64) -> Result<Object, EvalError> {
+    match arg {
+        Object::Integer(_) | Object::BigInt(_) => Ok(arg),
+        Object::Float(x) => Ok(Object::Float(round(x))),
+        _ => Err(expected_number(arg)),
+    }
+}
+
+// Integer powers with a non-negative exponent are computed exactly, up to
+// MAX_INTEGER_BITS bits, everything else goes through f64::powf
+fn pow(base: Object, exponent: Object) -> Result<Object, EvalError> {
+    match (base, exponent) {
+        (base @ (Object::Integer(_) | Object::BigInt(_)), Object::Integer(e)) if e >= 0 => {
+            exact_pow(to_big_integer(base), BigInt::from(e))
+        }
+        (base @ (Object::Integer(_) | Object::BigInt(_)), Object::BigInt(e)) if e.is_positive() => {
+            exact_pow(to_big_integer(base), e)
+        }
+        (base, exponent) => Ok(Object::Float(to_float(base)?.powf(to_float(exponent)?))),
+    }
+}
+
+fn exact_pow(base: BigInt, exponent: BigInt) -> Result<Object, EvalError> {
+    // 0, 1 and -1 only need the parity of the exponent
+    if base.bits() <= 1 {
+        let exponent = match exponent.to_u32() {
+            Some(0) => 0,
+            _ if exponent.bit(0) => 1,
+            _ => 2,
+        };
+        return big_integer(base.pow(exponent));
+    }
+
+    // The result has more than (bits - 1) * exponent bits, the ones known to
+    // be too large are rejected before computing them
+    let exponent = exponent.to_u64().ok_or_else(integer_too_large)?;
+    if (base.bits() - 1).saturating_mul(exponent) >= MAX_INTEGER_BITS {
+        return Err(integer_too_large());
+    }
+    // A step for each 64 bits of the result, as for the infix operators
+    limits::steps(base.bits() * exponent / 64)?;
+    allocate_integer(Meter::Program, base.bits() * exponent)?;
+    big_integer(base.pow(exponent as u32))
+}
+
+pub(super) fn to_float(obj: Object) -> Result<f64, EvalError> {
+    match obj {
+        Object::Integer(i) => Ok(i as f64),
+        // Values beyond the f64 range become +/-inf
+        Object::BigInt(i) => Ok(i.to_f64().unwrap_or(if i.is_negative() {
+            f64::NEG_INFINITY
+        } else {
+            f64::INFINITY
+        })),
+        Object::Float(x) => Ok(x),
+        _ => Err(expected_number(obj)),
+    }
+}
+
+fn expected_number(got: Object) -> EvalError {
+    EvalError::MismatchedObject {
+        expected: "INTEGER or FLOAT".to_string(),
+        got,
+    }
+}
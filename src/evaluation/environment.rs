@@ -1,44 +1,194 @@
 use crate::object::Object;
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::{Rc, Weak},
+};
 
-#[derive(Clone, Debug, PartialEq)]
+// Registered environments are collected after this many have been created
+// since the last collection, or twice as many as survived it
+const COLLECTION_THRESHOLD: usize = 1000;
+
+thread_local! {
+    // Every environment made by new_rc or with_outer, dead ones are pruned
+    // when collecting
+    static REGISTRY: RefCell<Vec<Weak<Environment>>> = const { RefCell::new(vec![]) };
+    static NEXT_COLLECTION: Cell<usize> = const { Cell::new(COLLECTION_THRESHOLD) };
+}
+
+// Closures keep an Rc to the environment they were created in, never a copy:
+// bindings added or changed there later, including the function's own name,
+// are visible when the closure runs. A call runs in a new environment whose
+// outer is the closure's environment
+#[derive(Default)]
 pub struct Environment {
-    store: Rc<RefCell<HashMap<String, Object>>>,
+    store: RefCell<HashMap<String, Object>>,
     outer: Option<Rc<Environment>>,
 }
 
-impl std::default::Default for Environment {
-    fn default() -> Self {
-        Self {
-            store: Rc::new(RefCell::new(HashMap::new())),
-            outer: None,
+// Environments are compared by identity, and their Debug output lists names
+// only: a function bound in the environment it captured is a cycle
+impl PartialEq for Environment {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+// Environments only reachable through the one dropped are dropped in a loop
+// rather than recursively, lists made of closures can be arbitrarily long
+impl Drop for Environment {
+    fn drop(&mut self) {
+        let mut pending = vec![];
+        self.take_references(&mut pending);
+
+        while let Some(env) = pending.pop() {
+            if let Ok(mut env) = Rc::try_unwrap(env) {
+                env.take_references(&mut pending);
+            }
         }
     }
 }
 
+impl std::fmt::Debug for Environment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let store = self.store.borrow();
+        let mut names = store.keys().collect::<Vec<_>>();
+        names.sort();
+
+        f.debug_struct("Environment")
+            .field("names", &names)
+            .field("outer", &self.outer)
+            .finish()
+    }
+}
+
 impl Environment {
     pub fn new_rc() -> Rc<Environment> {
-        Rc::new(Self::default())
+        Self::register(Self::default())
     }
 
     pub(super) fn with_outer(outer: Rc<Environment>) -> Rc<Environment> {
-        Rc::new(Self {
+        Self::register(Self {
+            store: RefCell::default(),
             outer: Some(outer),
-            ..Default::default()
         })
     }
 
-    pub(super) fn get(&self, name: impl AsRef<str>) -> Object {
-        self.store
-            .borrow()
-            .get(name.as_ref())
-            .unwrap_or({
-                &match self.outer.clone() {
-                    Some(outer) => outer.get(name),
-                    None => Object::Null,
+    fn register(env: Environment) -> Rc<Environment> {
+        let registered = REGISTRY.with_borrow(|registry| registry.len());
+        if registered >= NEXT_COLLECTION.get() {
+            let live = registered - Self::collect_cycles();
+            NEXT_COLLECTION.set(COLLECTION_THRESHOLD.max(2 * live));
+        }
+
+        let env = Rc::new(env);
+        REGISTRY.with_borrow_mut(|registry| registry.push(Rc::downgrade(&env)));
+        env
+    }
+
+    // Environments of this thread that are still allocated
+    pub fn live_count() -> usize {
+        REGISTRY
+            .with_borrow(|registry| registry.iter().filter(|env| env.strong_count() > 0).count())
+    }
+
+    // Frees the environments only kept alive by each other, such as the
+    // environment of a recursive function and that function, and returns how
+    // many registered environments were freed or already dead.
+    //
+    // Environments held from outside the registered ones (the REPL, the Rust
+    // stack of a running evaluation, ...) are found by subtracting the
+    // references environments hold to each other from their reference count.
+    // Everything those reach is kept, the stores of the others are emptied,
+    // which breaks their cycles
+    pub fn collect_cycles() -> usize {
+        let registered = REGISTRY.take();
+        let envs = registered
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect::<Vec<_>>();
+        let index = envs
+            .iter()
+            .enumerate()
+            .map(|(i, env)| (Rc::as_ptr(env), i))
+            .collect::<HashMap<_, _>>();
+        let index_of = |env: &Rc<Environment>| index.get(&Rc::as_ptr(env)).copied();
+
+        let mut internal = vec![0; envs.len()];
+        for env in &envs {
+            env.for_each_reference(&mut |other| {
+                if let Some(i) = index_of(other) {
+                    internal[i] += 1;
                 }
-            })
-            .to_owned()
+            });
+        }
+
+        // One reference to each is held by envs
+        let mut reachable = envs
+            .iter()
+            .zip(&internal)
+            .map(|(env, internal)| Rc::strong_count(env) - 1 > *internal)
+            .collect::<Vec<_>>();
+        let mut stack = (0..envs.len())
+            .filter(|i| reachable[*i])
+            .collect::<Vec<_>>();
+        while let Some(i) = stack.pop() {
+            envs[i].for_each_reference(&mut |other| {
+                if let Some(j) = index_of(other) {
+                    if !reachable[j] {
+                        reachable[j] = true;
+                        stack.push(j);
+                    }
+                }
+            });
+        }
+
+        // Dropped once no store is borrowed anymore
+        let garbage = envs
+            .iter()
+            .zip(&reachable)
+            .filter(|(_, reachable)| !**reachable)
+            .map(|(env, _)| env.store.take())
+            .collect::<Vec<_>>();
+
+        let survivors = envs
+            .iter()
+            .zip(&reachable)
+            .filter(|(_, reachable)| **reachable)
+            .map(|(env, _)| Rc::downgrade(env))
+            .collect::<Vec<_>>();
+        let freed = registered.len() - survivors.len();
+
+        REGISTRY.set(survivors);
+        drop(garbage);
+        drop(envs);
+        freed
+    }
+
+    fn take_references(&mut self, pending: &mut Vec<Rc<Environment>>) {
+        pending.extend(self.outer.take());
+        for (_, value) in self.store.get_mut().drain() {
+            value.take_environments(pending);
+        }
+    }
+
+    // The environments this one holds a reference to: its outer one and the
+    // ones captured by the functions bound in it
+    fn for_each_reference(&self, f: &mut impl FnMut(&Rc<Environment>)) {
+        if let Some(outer) = &self.outer {
+            f(outer);
+        }
+        for value in self.store.borrow().values() {
+            value.for_each_environment(f);
+        }
+    }
+
+    // None when the name is not bound here or in any outer environment
+    pub(super) fn get(&self, name: impl AsRef<str>) -> Option<Object> {
+        match self.store.borrow().get(name.as_ref()) {
+            Some(value) => Some(value.clone()),
+            None => self.outer.as_ref()?.get(name),
+        }
     }
 
     pub(super) fn set(&self, name: impl ToString, value: Object) -> Object {
@@ -0,0 +1,338 @@
+use super::EvalError;
+use crate::object::Object;
+
+// Codes are stable: a variant keeps its code, new variants get the next one
+// and the codes of removed variants are not reused
+impl EvalError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            EvalError::MismatchedTypes { .. } => "E0001",
+            EvalError::UnknownPrefix { .. } => "E0002",
+            EvalError::InfixBooleanOperator { .. } => "E0003",
+            EvalError::UnknownInfix { .. } => "E0004",
+            EvalError::IdentifierNotFound(_) => "E0005",
+            EvalError::MismatchedObject { .. } => "E0006",
+            EvalError::InvalidNumberArguments { .. } => "E0007",
+            EvalError::DivisionByZero => "E0008",
+            EvalError::StackOverflow { .. } => "E0009",
+            EvalError::StepLimit { .. } => "E0010",
+            EvalError::Timeout(_) => "E0011",
+            EvalError::Interrupted => "E0012",
+            EvalError::OutOfMemory { .. } => "E0013",
+            EvalError::InvalidShiftAmount(_) => "E0014",
+            EvalError::Custom(_) => "E0015",
+            EvalError::Unhandled => "E0016",
+            EvalError::Thrown(_) => "E0017",
+            EvalError::IntegerTooLarge { .. } => "E0018",
+            EvalError::ResultTooDeep { .. } => "E0019",
+        }
+    }
+
+    // The kind of the error object a catch clause gets
+    pub fn kind(&self) -> &str {
+        match self {
+            EvalError::MismatchedTypes { .. } => "MismatchedTypes",
+            EvalError::UnknownPrefix { .. } => "UnknownPrefix",
+            EvalError::InfixBooleanOperator { .. } => "InfixBooleanOperator",
+            EvalError::UnknownInfix { .. } => "UnknownInfix",
+            EvalError::IdentifierNotFound(_) => "IdentifierNotFound",
+            EvalError::MismatchedObject { .. } => "MismatchedObject",
+            EvalError::InvalidNumberArguments { .. } => "InvalidNumberArguments",
+            EvalError::DivisionByZero => "DivisionByZero",
+            EvalError::StackOverflow { .. } => "StackOverflow",
+            EvalError::StepLimit { .. } => "StepLimit",
+            EvalError::Timeout(_) => "Timeout",
+            EvalError::Interrupted => "Interrupted",
+            EvalError::OutOfMemory { .. } => "OutOfMemory",
+            EvalError::InvalidShiftAmount(_) => "InvalidShiftAmount",
+            EvalError::Custom(_) => "Error",
+            EvalError::Unhandled => "Unhandled",
+            EvalError::Thrown(thrown) => match &**thrown {
+                Object::Error { kind, .. } => kind,
+                _ => "Error",
+            },
+            EvalError::IntegerTooLarge { .. } => "IntegerTooLarge",
+            EvalError::ResultTooDeep { .. } => "ResultTooDeep",
+        }
+    }
+
+    // The limits set by the host stop the program, catch and finally clauses
+    // do not run
+    pub(crate) fn is_catchable(&self) -> bool {
+        !matches!(
+            self,
+            EvalError::StepLimit { .. }
+                | EvalError::Timeout(_)
+                | EvalError::Interrupted
+                | EvalError::OutOfMemory { .. }
+        )
+    }
+}
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::MismatchedTypes {
+                left,
+                operator,
+                right,
+            } => write!(
+                f,
+                "type mismatch: {} {} {}",
+                left.object_type(),
+                operator,
+                right.object_type()
+            ),
+            EvalError::UnknownPrefix { operator, value } => {
+                write!(f, "unknown operator: {}{}", operator, value.object_type())
+            }
+            EvalError::InfixBooleanOperator { operator, .. } => {
+                write!(f, "unknown operator: BOOLEAN {} BOOLEAN", operator)
+            }
+            EvalError::UnknownInfix {
+                left,
+                operator,
+                right,
+            } => write!(
+                f,
+                "unknown operator: {} {} {}",
+                left.object_type(),
+                operator,
+                right.object_type()
+            ),
+            EvalError::IdentifierNotFound(name) => write!(f, "identifier not found: {}", name),
+            EvalError::MismatchedObject { expected, got } => {
+                write!(f, "expected {}, got {}", expected, got.object_type())
+            }
+            EvalError::InvalidNumberArguments { expected, got } => write!(
+                f,
+                "wrong number of arguments: expected {}, got {}",
+                expected, got
+            ),
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::StackOverflow { depth } => {
+                write!(f, "stack overflow: more than {} nested calls", depth)
+            }
+            EvalError::StepLimit { steps } => {
+                write!(f, "step limit exceeded: more than {} steps", steps)
+            }
+            EvalError::Timeout(timeout) => write!(f, "timeout: ran for more than {:?}", timeout),
+            EvalError::Interrupted => write!(f, "interrupted"),
+            EvalError::OutOfMemory { limit } => {
+                write!(f, "out of memory: more than {} bytes allocated", limit)
+            }
+            EvalError::InvalidShiftAmount(amount) => {
+                write!(f, "invalid shift amount: {}, expected 0 or more", amount)
+            }
+            EvalError::Custom(message) => write!(f, "{}", message),
+            EvalError::Unhandled => write!(f, "unhandled expression"),
+            EvalError::Thrown(thrown) => write!(f, "{}", thrown),
+            EvalError::IntegerTooLarge { bits } => {
+                write!(f, "integer too large: more than {} bits", bits)
+            }
+            EvalError::ResultTooDeep { depth } => {
+                write!(f, "result too deep: more than {} nested results", depth)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+// Longer description of an error code, for the explain command
+pub(crate) fn explain(code: &str) -> Option<&'static str> {
+    Some(match code.to_uppercase().as_str() {
+        "E0001" => {
+            "\
+type mismatch
+
+An infix operator was applied to values of types it cannot combine, such as
+an integer and a boolean, or floats given to a bitwise operator. Integers
+and floats mix in arithmetic and comparisons.
+
+    1 + true    // type mismatch: INTEGER + BOOLEAN
+    1.5 & 1.0   // type mismatch: FLOAT & FLOAT
+    1 + 2.5     // 3.5"
+        }
+        "E0002" => {
+            "\
+unknown prefix operator
+
+A prefix operator was applied to a value it does not support: `-` needs a
+number and `~` an integer. `!` takes any value, null and false being the
+only false ones.
+
+    -true       // unknown operator: -BOOLEAN
+    ~1.5        // unknown operator: ~FLOAT"
+        }
+        "E0003" => {
+            "\
+unknown operator on booleans
+
+Booleans only support `==` and `!=`. Arithmetic, comparison and bitwise
+operators are not defined on them.
+
+    true + false    // unknown operator: BOOLEAN + BOOLEAN"
+        }
+        "E0004" => {
+            "\
+unknown infix operator
+
+The operands have the same type, but the operator is not defined for it.
+Strings have no operators.
+
+    \"a\" + \"b\"   // unknown operator: STRING + STRING"
+        }
+        "E0005" => {
+            "\
+identifier not found
+
+The name is neither bound by a let or a parameter visible from where it is
+used, nor the name of a builtin. Functions see the bindings of the scope
+they were created in, including the ones made after them.
+
+    let f = fn() { y };
+    f()         // identifier not found: y"
+        }
+        "E0006" => {
+            "\
+unexpected value
+
+A value of the wrong type was given where a specific type is needed: a
+call of something that is not a function, or a builtin argument of the
+wrong type.
+
+    let x = 1;
+    x()         // expected FUNCTION, got INTEGER
+    sqrt(\"a\")   // expected INTEGER or FLOAT, got STRING"
+        }
+        "E0007" => {
+            "\
+wrong number of arguments
+
+A function was called with more or fewer arguments than it has parameters.
+There are no default or variadic parameters.
+
+    let add = fn(a, b) { a + b };
+    add(1)      // wrong number of arguments: expected 2, got 1"
+        }
+        "E0008" => {
+            "\
+division by zero
+
+An integer was divided by zero. Dividing a float by zero gives an infinity
+or NaN instead.
+
+    1 / 0       // division by zero
+    1.0 / 0     // inf"
+        }
+        "E0009" => {
+            "\
+stack overflow
+
+More calls were in progress at once than the maximum call depth allows,
+10000 by default. This is usually a recursion without a base case. Calls
+in tail position, returned or last in a function body, do not count, so
+a recursion can often be made to run at any depth by moving the recursive
+call there. The limit is set with --max-call-depth.
+
+    let f = fn(n) { 1 + f(n) };
+    f(0)        // stack overflow: more than 10000 nested calls"
+        }
+        "E0010" => {
+            "\
+step limit exceeded
+
+The program evaluated more statements and expressions than allowed. The
+limit is set with --max-steps, and counts from the start of each program."
+        }
+        "E0011" => {
+            "\
+timeout
+
+The program ran for longer than allowed. The limit is set with
+--timeout-ms, and counts from the start of each program."
+        }
+        "E0012" => {
+            "\
+interrupted
+
+The host stopped the program with an Interrupt handle, see Limits."
+        }
+        "E0013" => {
+            "\
+out of memory
+
+The program allocated more bytes for strings, big integers, environments,
+bindings, results and errors than allowed. This is a budget of allocations rather than a
+bound on the memory in use: the bytes freed since are still counted. The
+limit is set with --max-allocated, and counts over all the programs of an
+interpreter or REPL session, whose bindings stay allocated.
+
+    let f = fn(n) { let a = n; f(n + 1) };
+    f(0)        // out of memory"
+        }
+        "E0014" => {
+            "\
+invalid shift amount
+
+Integers are shifted by 0 bits or more, the amount was negative. Left shifts
+past 64 bits give arbitrary precision integers, right shifts by 64 bits or
+more leave 0 or -1.
+
+    1 << -1     // invalid shift amount: -1, expected 0 or more
+    1 << 64     // 18446744073709551616"
+        }
+        "E0015" => {
+            "\
+error
+
+An error with a message of its own, the message describes it."
+        }
+        "E0016" => {
+            "\
+unhandled expression
+
+The evaluator met an expression it cannot evaluate, such as the
+placeholder left by a syntax error. This is a bug of the interpreter when
+the program parsed without errors."
+        }
+        "E0017" => {
+            "\
+uncaught exception
+
+A value was thrown and no try around it caught it. The message starts with
+the kind of the error: the one given to error(), or Error for other values.
+
+    throw \"oops\"                      // Error: oops
+    throw error(\"Parse\", \"bad digit\") // Parse: bad digit
+
+Runtime errors can be caught too, except the ones of the limits set with
+--max-steps, --timeout-ms and --max-allocated:
+
+    try { 1 / 0 } catch (e) { kind(e) }   // DivisionByZero"
+        }
+        "E0018" => {
+            "\
+integer too large
+
+An integer result would have more than 1048576 bits. Integers grow past 64
+bits as needed, up to this size, which bounds the time and memory a single
+operation takes. Floats are not limited this way.
+
+    pow(2, 4000000000)      // integer too large: more than 1048576 bits
+    pow(2.0, 4000000000)    // inf"
+        }
+        "E0019" => {
+            "\
+result too deep
+
+A result would hold more than 100 results nested in each other, such as
+ok(ok(ok(...))). The value of a result can be any other value.
+
+    let f = fn(n, r) { if (n == 0) { r } else { f(n - 1, ok(r)) } };
+    f(1000, 1)      // result too deep: more than 100 nested results"
+        }
+        _ => return None,
+    })
+}
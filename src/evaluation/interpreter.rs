@@ -0,0 +1,70 @@
+use super::{environment::Environment, limits::Limits, trace::RuntimeError};
+use crate::{lexer::Lexer, object::Object, parser::Parser, ParserError};
+use std::{cell::Cell, rc::Rc};
+
+// Entry point for hosts: evaluates sources one after the other in the same
+// environment, so that the bindings of one are seen by the next, each within
+// the limits of the interpreter
+#[derive(Debug)]
+pub struct Interpreter {
+    environment: Rc<Environment>,
+    limits: Limits,
+    // By all the sources so far, which the bindings they keep add up to
+    allocated: Cell<usize>,
+}
+
+// Why a source could not be evaluated
+#[derive(Clone, Debug)]
+pub enum ProgramError {
+    // Every syntax error of the source, nothing was evaluated
+    Parse(Vec<ParserError>),
+    Runtime(RuntimeError),
+}
+
+impl std::fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProgramError::Parse(errors) => {
+                let errors: Vec<String> = errors.iter().map(ParserError::to_string).collect();
+                write!(f, "{}", errors.join("\n"))
+            }
+            ProgramError::Runtime(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ProgramError {}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new(Limits::default())
+    }
+}
+
+impl Interpreter {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            environment: Environment::new_rc(),
+            limits,
+            allocated: Cell::new(0),
+        }
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub fn eval(&self, source: &str) -> Result<Object, ProgramError> {
+        let mut parser = Parser::new(Lexer::from_reader(source.as_bytes()));
+        let program = parser.parse_program().unwrap_or_default();
+        if !parser.errors.is_empty() {
+            return Err(ProgramError::Parse(parser.errors));
+        }
+
+        let mut allocated = self.allocated.get();
+        let result = program.eval_allocated(self.environment.clone(), &self.limits, &mut allocated);
+        self.allocated.set(allocated);
+
+        result.map_err(ProgramError::Runtime)
+    }
+}
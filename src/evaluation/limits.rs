@@ -0,0 +1,219 @@
+use super::EvalError;
+use std::{
+    cell::{Cell, RefCell},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+// Stack left when a call checks it, enough for one level of evaluation in
+// debug builds, and stack added when there is less
+const RED_ZONE: usize = 256 * 1024;
+const STACK_GROWTH: usize = 4 * 1024 * 1024;
+
+// Steps between two reads of the clock
+const CLOCK_INTERVAL: u64 = 1024;
+
+// Bounds on what a program may use, given to the Interpreter evaluating it.
+// The steps and the time are counted for each program from the start of its
+// evaluation, the allocations over all the programs of the interpreter
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limits {
+    // Calls in progress at once, tail calls replace their caller
+    pub max_call_depth: usize,
+    // Statements and expressions evaluated
+    pub max_steps: Option<u64>,
+    pub timeout: Option<Duration>,
+    // Budget of bytes allocated for strings, big integers, environments,
+    // bindings, results and errors. The bytes freed since still count, so that this bounds the
+    // allocations made rather than the memory in use
+    pub max_allocated: Option<usize>,
+    pub interrupt: Option<Interrupt>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_call_depth: 10_000,
+            max_steps: None,
+            timeout: None,
+            max_allocated: None,
+            interrupt: None,
+        }
+    }
+}
+
+// Handle to stop an evaluation from another thread. The evaluation fails
+// with EvalError::Interrupted at its next step. The handle stays interrupted,
+// so that an interrupt made just before an evaluation starts stops it too,
+// until the host resets it
+#[derive(Clone, Debug, Default)]
+pub struct Interrupt(Arc<AtomicBool>);
+
+impl PartialEq for Interrupt {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Interrupt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// What the program being evaluated on this thread has used, and its limits
+struct Budget {
+    max_call_depth: usize,
+    steps: u64,
+    max_steps: Option<u64>,
+    deadline: Option<(Instant, Duration)>,
+    allocated: usize,
+    max_allocated: Option<usize>,
+    interrupt: Option<Interrupt>,
+}
+
+impl Budget {
+    fn new(limits: &Limits, allocated: usize) -> Self {
+        Budget {
+            max_call_depth: limits.max_call_depth,
+            steps: 0,
+            max_steps: limits.max_steps,
+            deadline: limits
+                .timeout
+                .map(|timeout| (Instant::now() + timeout, timeout)),
+            allocated,
+            max_allocated: limits.max_allocated,
+            interrupt: limits.interrupt.clone(),
+        }
+    }
+}
+
+thread_local! {
+    static CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+    static BUDGET: RefCell<Budget> = RefCell::new(Budget::new(&Limits::default(), 0));
+}
+
+// Starts counting the steps and the time of a program, within its limits.
+// The bytes allocated add up to the ones of the earlier programs
+pub(super) fn start_program(limits: &Limits, allocated: usize) {
+    BUDGET.set(Budget::new(limits, allocated));
+}
+
+// Bytes allocated so far, by the program and the earlier ones
+pub(super) fn allocated() -> usize {
+    BUDGET.with_borrow(|budget| budget.allocated)
+}
+
+// Counts the evaluation of a statement or an expression
+pub(super) fn step() -> Result<(), EvalError> {
+    steps(1)
+}
+
+// Counts work worth several steps, such as arithmetic on big integers, before
+// doing it
+pub(super) fn steps(count: u64) -> Result<(), EvalError> {
+    BUDGET.with_borrow_mut(|budget| {
+        let previous = budget.steps;
+        budget.steps = budget.steps.saturating_add(count);
+
+        if let Some(interrupt) = &budget.interrupt {
+            if interrupt.is_interrupted() {
+                return Err(EvalError::Interrupted);
+            }
+        }
+        if let Some(max_steps) = budget.max_steps {
+            if budget.steps > max_steps {
+                return Err(EvalError::StepLimit { steps: max_steps });
+            }
+        }
+        if let Some((deadline, timeout)) = budget.deadline {
+            if previous / CLOCK_INTERVAL != budget.steps / CLOCK_INTERVAL
+                && Instant::now() >= deadline
+            {
+                return Err(EvalError::Timeout(timeout));
+            }
+        }
+        Ok(())
+    })
+}
+
+// Counts bytes the program is about to allocate, which it must not do when
+// this fails
+pub(super) fn allocate(bytes: usize) -> Result<(), EvalError> {
+    BUDGET.with_borrow_mut(|budget| {
+        budget.allocated = budget.allocated.saturating_add(bytes);
+        match budget.max_allocated {
+            Some(max_allocated) if budget.allocated > max_allocated => {
+                Err(EvalError::OutOfMemory {
+                    limit: max_allocated,
+                })
+            }
+            _ => Ok(()),
+        }
+    })
+}
+
+// Counts the arithmetic of the program evaluated on this thread. The optimizer
+// folds constants outside of any program and counts nothing, so that what it
+// folds does not depend on the programs run before
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Meter {
+    Program,
+    Unmetered,
+}
+
+impl Meter {
+    pub(super) fn steps(self, count: u64) -> Result<(), EvalError> {
+        match self {
+            Meter::Program => steps(count),
+            Meter::Unmetered => Ok(()),
+        }
+    }
+
+    pub(super) fn allocate(self, bytes: usize) -> Result<(), EvalError> {
+        match self {
+            Meter::Program => allocate(bytes),
+            Meter::Unmetered => Ok(()),
+        }
+    }
+}
+
+// Runs a call one level deeper, on a stack grown when needed so that the
+// depth limit is reached before the Rust stack overflows
+pub(super) fn with_call_depth<T>(
+    call: impl FnOnce() -> Result<T, EvalError>,
+) -> Result<T, EvalError> {
+    let max_call_depth = BUDGET.with_borrow(|budget| budget.max_call_depth);
+    if CALL_DEPTH.get() >= max_call_depth {
+        return Err(EvalError::StackOverflow {
+            depth: max_call_depth,
+        });
+    }
+
+    // Restores the depth on every way out of the call
+    struct Depth;
+    impl Drop for Depth {
+        fn drop(&mut self) {
+            CALL_DEPTH.set(CALL_DEPTH.get() - 1);
+        }
+    }
+
+    CALL_DEPTH.set(CALL_DEPTH.get() + 1);
+    let _depth = Depth;
+    stacker::maybe_grow(RED_ZONE, STACK_GROWTH, call)
+}
@@ -1,19 +1,37 @@
+// EvalError carries the offending Objects by value
+#![allow(clippy::result_large_err)]
+
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
 mod tests;
 
+pub(crate) mod builtins;
 pub mod environment;
+pub(crate) mod error;
+pub(crate) mod interpreter;
+pub mod limits;
+mod tail;
+pub(crate) mod trace;
 
 use std::rc::Rc;
 
+use builtins::{to_float, Builtin};
 use environment::Environment;
+use limits::{Limits, Meter};
+use tail::Flow;
+use trace::{Frame, RuntimeError};
 
 use crate::{
     ast::{Expression, InfixOperator, PrefixOperator, Program, Statement},
     object::Object,
+    token::Span,
 };
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive};
+use std::str::FromStr;
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) enum EvalError {
+pub enum EvalError {
     MismatchedTypes {
         left: Object,
         operator: InfixOperator,
@@ -28,6 +46,11 @@ pub(crate) enum EvalError {
         operator: InfixOperator,
         right: bool,
     },
+    UnknownInfix {
+        left: Object,
+        operator: InfixOperator,
+        right: Object,
+    },
     IdentifierNotFound(String),
     MismatchedObject {
         expected: String,
@@ -37,34 +60,87 @@ pub(crate) enum EvalError {
         expected: u64,
         got: u64,
     },
+    DivisionByZero,
+    // More calls in progress than Limits::max_call_depth
+    StackOverflow {
+        depth: usize,
+    },
+    // More steps than Limits::max_steps
+    StepLimit {
+        steps: u64,
+    },
+    // Ran for longer than Limits::timeout
+    Timeout(std::time::Duration),
+    // Stopped with an Interrupt handle
+    Interrupted,
+    // More bytes allocated than Limits::max_allocated
+    OutOfMemory {
+        limit: usize,
+    },
+    // Shift amounts must not be negative
+    InvalidShiftAmount(Object),
     Custom(String),
     Unhandled,
+    // Thrown by the program, always an Object::Error
+    Thrown(Box<Object>),
+    // Integers have at most MAX_INTEGER_BITS bits
+    IntegerTooLarge {
+        bits: u64,
+    },
+    // Results nest at most MAX_RESULT_DEPTH deep, see Builtin::MakeOk
+    ResultTooDeep {
+        depth: usize,
+    },
 }
 
 pub(crate) trait Eval {
     fn eval(self, environment: Rc<Environment>) -> Result<Object, EvalError>;
 }
 
-impl Eval for Program {
-    fn eval(self, environment: Rc<Environment>) -> Result<Object, EvalError> {
-        let mut result = Ok(Object::Null);
+// Not Eval, the error of a program comes with its stack trace
+impl Program {
+    pub(crate) fn eval(
+        self,
+        environment: Rc<Environment>,
+        limits: &Limits,
+    ) -> Result<Object, RuntimeError> {
+        self.eval_allocated(environment, limits, &mut 0)
+    }
 
-        for statement in self.statements {
-            result = statement.eval(environment.clone());
+    // Counts the bytes allocated on top of the ones of the earlier programs
+    // run in the same environment, the total is updated when it ends
+    pub(crate) fn eval_allocated(
+        self,
+        environment: Rc<Environment>,
+        limits: &Limits,
+        allocated: &mut usize,
+    ) -> Result<Object, RuntimeError> {
+        limits::start_program(limits, *allocated);
+        trace::clear();
+        let result = self.eval_statements(environment);
+        *allocated = limits::allocated();
+        result
+    }
 
-            match result.clone() {
+    fn eval_statements(self, environment: Rc<Environment>) -> Result<Object, RuntimeError> {
+        let mut result = Object::Null;
+
+        for statement in self.statements {
+            match statement.eval(environment.clone()) {
                 Ok(Object::ReturnValue { value }) => return Ok(*value),
-                Err(_) => return Ok(result?),
-                _ => {}
+                Ok(obj) => result = obj,
+                Err(error) => return Err(trace::traced(error)),
             }
         }
 
-        Ok(result?)
+        Ok(result)
     }
 }
 
+#[allow(clippy::needless_question_mark)]
 impl Eval for Statement {
     fn eval(self, environment: Rc<Environment>) -> Result<Object, EvalError> {
+        limits::step()?;
         match self {
             Statement::Expression(expression) => expression.eval(environment.clone()),
             Statement::Block(statements) => {
@@ -82,18 +158,37 @@ impl Eval for Statement {
 
                 Ok(result?)
             }
-            Statement::Return(expression) => {
-                expression.eval(environment).map(|obj| Object::ReturnValue {
+            Statement::Return(expression) => expression.eval(environment).map(|obj| match obj {
+                returned @ Object::ReturnValue { .. } => returned,
+                obj => Object::ReturnValue {
                     value: Box::new(obj),
-                })
+                },
+            }),
+            Statement::Throw(expression) => {
+                let thrown = expression.eval(environment)?;
+                if is_return(&thrown) {
+                    return Ok(thrown);
+                }
+                Err(EvalError::Thrown(Box::new(throw(thrown))))
             }
             Statement::Let { name, value } => {
-                let result = value.eval(environment.clone());
-                if result.is_err() {
-                    return Ok(result?);
+                let mut result = value.eval(environment.clone())?;
+                if is_return(&result) {
+                    return Ok(result);
                 }
 
-                let obj = environment.set(name, result?);
+                // Functions are named after the first let binding them, for
+                // stack traces
+                if let Object::Function {
+                    name: function_name,
+                    ..
+                } = &mut result
+                {
+                    function_name.get_or_insert_with(|| name.clone());
+                }
+
+                limits::allocate(name.len() + std::mem::size_of::<Object>())?;
+                let obj = environment.set(name, result);
 
                 Ok(obj)
             }
@@ -103,12 +198,25 @@ impl Eval for Statement {
 
 impl Eval for Expression {
     fn eval(self, environment: Rc<Environment>) -> Result<Object, EvalError> {
+        limits::step()?;
         match self {
             Expression::Int(i) => Ok(Object::Integer(i)),
+            Expression::BigInt(i) => {
+                allocate_integer(Meter::Program, i.bits())?;
+                Ok(Object::from(i))
+            }
+            Expression::Float(x) => Ok(Object::Float(x)),
+            Expression::Str(str) => {
+                limits::allocate(str.len())?;
+                Ok(Object::Str(str.into()))
+            }
             Expression::Bool(b) => Ok(Object::Bool(b)),
             Expression::Prefix { operator, right } => {
                 let right = right.eval(environment)?;
-                eval_expr_prefix(operator, right)
+                if is_return(&right) {
+                    return Ok(right);
+                }
+                eval_expr_prefix(operator, right, Meter::Program)
             }
             Expression::Infix {
                 left,
@@ -116,8 +224,14 @@ impl Eval for Expression {
                 right,
             } => {
                 let left = left.eval(environment.clone())?;
+                if is_return(&left) {
+                    return Ok(left);
+                }
                 let right = right.eval(environment.clone())?;
-                eval_expr_infix(operator, left, right)
+                if is_return(&right) {
+                    return Ok(right);
+                }
+                eval_expr_infix(operator, left, right, Meter::Program)
             }
             Expression::If {
                 condition,
@@ -125,6 +239,9 @@ impl Eval for Expression {
                 alternative,
             } => {
                 let condition = condition.eval(environment.clone())?;
+                if is_return(&condition) {
+                    return Ok(condition);
+                }
 
                 if is_true(condition) {
                     Statement::Block(consequence).eval(environment.clone())
@@ -134,42 +251,167 @@ impl Eval for Expression {
                     Ok(Object::Null)
                 }
             }
-            Expression::Identifier(str) => {
-                let val = environment.get(&str);
-
-                if val == Object::Null {
-                    return Err(EvalError::IdentifierNotFound(str.to_string()));
+            Expression::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                let mut result = Statement::Block(body).eval(environment.clone());
+
+                if let Some((name, handler)) = catch {
+                    result = match result {
+                        Err(error) if error.is_catchable() => {
+                            limits::allocate(name.len() + std::mem::size_of::<Object>())?;
+                            environment.set(name, caught(error));
+                            Statement::Block(handler).eval(environment.clone())
+                        }
+                        result => result,
+                    };
                 }
 
-                return Ok(val);
+                match finally {
+                    Some(finally) => eval_finally(result, finally, environment),
+                    None => result,
+                }
+            }
+            Expression::Identifier(str) => {
+                // Bindings shadow builtins of the same name
+                match environment.get(&str) {
+                    Some(val) => Ok(val),
+                    None => Builtin::from_str(&str)
+                        .map(Object::Builtin)
+                        .map_err(|_| EvalError::IdentifierNotFound(str.to_string())),
+                }
             }
             Expression::Function { parameters, body } => Ok(Object::Function {
+                name: None,
                 parameters,
                 body,
-                env: (*environment).clone(),
+                env: environment,
             }),
             Expression::FunctionCall {
                 function,
                 arguments,
+                span,
             } => {
                 let obj_fn = function.eval(environment.clone())?;
+                if is_return(&obj_fn) {
+                    return Ok(obj_fn);
+                }
 
-                let arguments: Vec<Object> = arguments
-                    .into_iter()
-                    .map(|arg| arg.eval(environment.clone()))
-                    .collect::<Result<Vec<_>, _>>()?;
-
-                apply_function(obj_fn, arguments)
+                match eval_arguments(arguments, &environment)? {
+                    Ok(arguments) => apply_function(obj_fn, arguments, span.0),
+                    Err(returned) => Ok(returned),
+                }
             }
+            Expression::Propagate(value) => match value.eval(environment)? {
+                Object::Ok { value } => Ok(*value),
+                // Returned from the enclosing function, like a return statement
+                err @ Object::Err { .. } => Ok(Object::ReturnValue {
+                    value: Box::new(err),
+                }),
+                returned @ Object::ReturnValue { .. } => Ok(returned),
+                value => Err(EvalError::MismatchedObject {
+                    expected: "RESULT".to_string(),
+                    got: value,
+                }),
+            },
             _ => Err(EvalError::Unhandled),
         }
     }
 }
 
-fn eval_expr_prefix(operator: PrefixOperator, right: Object) -> Result<Object, EvalError> {
+// A ? that met an err returns from the function whatever expression it is
+// in: an operand giving a ReturnValue makes the expression give it too
+fn is_return(obj: &Object) -> bool {
+    matches!(obj, Object::ReturnValue { .. })
+}
+
+// The values of the arguments of a call, or the ReturnValue of one of them
+fn eval_arguments(
+    arguments: Vec<Expression>,
+    environment: &Rc<Environment>,
+) -> Result<Result<Vec<Object>, Object>, EvalError> {
+    let mut values = Vec::with_capacity(arguments.len());
+    for argument in arguments {
+        let value = argument.eval(environment.clone())?;
+        if is_return(&value) {
+            return Ok(Err(value));
+        }
+        values.push(value);
+    }
+    Ok(Ok(values))
+}
+
+// Values other than errors are thrown as errors of kind Error. The stack is
+// set on the first throw, rethrowing an error keeps it
+fn throw(value: Object) -> Object {
+    match value {
+        Object::Error {
+            kind,
+            message,
+            stack,
+        } if stack.is_empty() => Object::Error {
+            kind,
+            message,
+            stack: trace::current(),
+        },
+        Object::Error { .. } => value,
+        value => Object::Error {
+            kind: "Error".into(),
+            message: value.to_string().into(),
+            stack: trace::current(),
+        },
+    }
+}
+
+// The error object a catch clause binds
+fn caught(error: EvalError) -> Object {
+    let trace = trace::take();
+    match error {
+        EvalError::Thrown(thrown) => *thrown,
+        error => Object::Error {
+            kind: error.kind().into(),
+            message: error.to_string().into(),
+            // Errors outside of any call inside the try happen where it is
+            stack: trace.unwrap_or_else(trace::current),
+        },
+    }
+}
+
+// The finally clause runs however the try and catch clauses ended, the limits
+// excepted. A return or an error in it replaces their result, otherwise
+// their result goes on, be it a value, a return or an error
+fn eval_finally(
+    result: Result<Object, EvalError>,
+    finally: Vec<Statement>,
+    environment: Rc<Environment>,
+) -> Result<Object, EvalError> {
+    if matches!(&result, Err(error) if !error.is_catchable()) {
+        return result;
+    }
+
+    // Errors caught inside the finally clause must not take the trace of the
+    // pending one
+    let pending_trace = trace::take();
+    match Statement::Block(finally).eval(environment)? {
+        returned @ Object::ReturnValue { .. } => Ok(returned),
+        _ => {
+            trace::restore(pending_trace);
+            result
+        }
+    }
+}
+
+pub(crate) fn eval_expr_prefix(
+    operator: PrefixOperator,
+    right: Object,
+    meter: Meter,
+) -> Result<Object, EvalError> {
     match operator {
         PrefixOperator::Bang => eval_expr_bang_operator(right),
-        PrefixOperator::Minus => eval_expr_minus_operator(right),
+        PrefixOperator::Minus => eval_expr_minus_operator(right, meter),
+        PrefixOperator::BitNot => eval_expr_bit_not_operator(right, meter),
     }
 }
 
@@ -177,6 +419,9 @@ fn eval_expr_bang_operator(right: Object) -> Result<Object, EvalError> {
     match right {
         Object::Bool(b) => Ok(Object::Bool(!b)),
         Object::Integer(i) => Ok(Object::Bool(i == 0)),
+        // A big integer is never zero
+        Object::BigInt(_) => Ok(Object::Bool(false)),
+        Object::Float(x) => Ok(Object::Bool(x == 0.0)),
         _ => Err(EvalError::UnknownPrefix {
             operator: PrefixOperator::Bang,
             value: right,
@@ -184,9 +429,17 @@ fn eval_expr_bang_operator(right: Object) -> Result<Object, EvalError> {
     }
 }
 
-fn eval_expr_minus_operator(right: Object) -> Result<Object, EvalError> {
+fn eval_expr_minus_operator(right: Object, meter: Meter) -> Result<Object, EvalError> {
     match right {
-        Object::Integer(i) => Ok(Object::Integer(-i)),
+        Object::Integer(i) => Ok(match i.checked_neg() {
+            Some(i) => Object::Integer(i),
+            None => Object::from(-BigInt::from(i)),
+        }),
+        Object::BigInt(i) => {
+            allocate_integer(meter, i.bits())?;
+            Ok(Object::from(-i))
+        }
+        Object::Float(x) => Ok(Object::Float(-x)),
         _ => Err(EvalError::UnknownPrefix {
             operator: PrefixOperator::Minus,
             value: right,
@@ -194,16 +447,52 @@ fn eval_expr_minus_operator(right: Object) -> Result<Object, EvalError> {
     }
 }
 
-fn eval_expr_infix(
+fn eval_expr_bit_not_operator(right: Object, meter: Meter) -> Result<Object, EvalError> {
+    match right {
+        Object::Integer(i) => Ok(Object::Integer(!i)),
+        Object::BigInt(i) => {
+            allocate_integer(meter, i.bits() + 1)?;
+            Ok(Object::from(!i))
+        }
+        _ => Err(EvalError::UnknownPrefix {
+            operator: PrefixOperator::BitNot,
+            value: right,
+        }),
+    }
+}
+
+pub(crate) fn eval_expr_infix(
     operator: InfixOperator,
     left: Object,
     right: Object,
+    meter: Meter,
 ) -> Result<Object, EvalError> {
     match (left.clone(), right.clone()) {
         (Object::Integer(left), Object::Integer(right)) => {
-            eval_expr_infix_integer(operator, left, right)
+            eval_expr_infix_integer(operator, left, right, meter)
+        }
+        (Object::Integer(_) | Object::BigInt(_), Object::Integer(_) | Object::BigInt(_)) => {
+            eval_expr_infix_big_integer(
+                operator,
+                to_big_integer(left),
+                to_big_integer(right),
+                meter,
+            )
+        }
+        // Any arithmetic involving a float is done in floating point
+        (Object::Float(_), Object::Integer(_) | Object::BigInt(_) | Object::Float(_))
+        | (Object::Integer(_) | Object::BigInt(_), Object::Float(_))
+            if !operator.is_bitwise() =>
+        {
+            eval_expr_infix_float(operator, to_float(left)?, to_float(right)?)
         }
         (Object::Bool(left), Object::Bool(right)) => eval_expr_infix_bool(operator, left, right),
+        // Strings are only values to pass around, no operator is defined on them
+        (left @ Object::Str(_), right @ Object::Str(_)) => Err(EvalError::UnknownInfix {
+            left,
+            operator,
+            right,
+        }),
         _ => Err(EvalError::MismatchedTypes {
             left,
             operator,
@@ -216,16 +505,178 @@ fn eval_expr_infix_integer(
     operator: InfixOperator,
     left: i64,
     right: i64,
+    meter: Meter,
+) -> Result<Object, EvalError> {
+    let result = match operator {
+        InfixOperator::Plus => left.checked_add(right),
+        InfixOperator::Minus => left.checked_sub(right),
+        InfixOperator::Mult => left.checked_mul(right),
+        InfixOperator::Division => {
+            if right == 0 {
+                return Err(EvalError::DivisionByZero);
+            }
+            left.checked_div(right)
+        }
+        InfixOperator::Equal => return Ok(Object::Bool(left == right)),
+        InfixOperator::NotEqual => return Ok(Object::Bool(left != right)),
+        InfixOperator::GreaterThan => return Ok(Object::Bool(left > right)),
+        InfixOperator::LessThan => return Ok(Object::Bool(left < right)),
+        InfixOperator::BitAnd => return Ok(Object::Integer(left & right)),
+        InfixOperator::BitOr => return Ok(Object::Integer(left | right)),
+        InfixOperator::BitXor => return Ok(Object::Integer(left ^ right)),
+        InfixOperator::ShiftLeft => match shift_amount(right)? {
+            amount if amount < u64::from(i64::BITS) => {
+                let shifted = left << amount;
+                // Bits shifted out of the i64 mean the result needs arbitrary precision
+                (shifted >> amount == left).then_some(shifted)
+            }
+            _ => None,
+        },
+        // Shifting every bit out leaves the sign
+        InfixOperator::ShiftRight => {
+            let amount = shift_amount(right)?.min(u64::from(i64::BITS) - 1);
+            return Ok(Object::Integer(left >> amount));
+        }
+    };
+
+    match result {
+        Some(i) => Ok(Object::Integer(i)),
+        // On overflow the operation is done again with arbitrary precision
+        None => {
+            eval_expr_infix_big_integer(operator, BigInt::from(left), BigInt::from(right), meter)
+        }
+    }
+}
+
+fn eval_expr_infix_big_integer(
+    operator: InfixOperator,
+    left: BigInt,
+    right: BigInt,
+    meter: Meter,
 ) -> Result<Object, EvalError> {
+    // The time taken grows with the size of the operands, which counts as a
+    // step for each 64 bits of them
+    meter.steps((left.bits() + right.bits()) / 64)?;
+    let widest = left.bits().max(right.bits());
+
     match operator {
-        InfixOperator::Plus => Ok(Object::Integer(left + right)),
-        InfixOperator::Minus => Ok(Object::Integer(left - right)),
-        InfixOperator::Mult => Ok(Object::Integer(left * right)),
-        InfixOperator::Division => Ok(Object::Integer(left / right)),
+        InfixOperator::Plus => {
+            allocate_integer(meter, widest + 1)?;
+            big_integer(left + right)
+        }
+        InfixOperator::Minus => {
+            allocate_integer(meter, widest + 1)?;
+            big_integer(left - right)
+        }
+        InfixOperator::Mult => {
+            // The product has at least this many bits
+            if (left.bits() + right.bits()).saturating_sub(1) > MAX_INTEGER_BITS {
+                return Err(integer_too_large());
+            }
+            allocate_integer(meter, left.bits() + right.bits())?;
+            big_integer(left * right)
+        }
+        InfixOperator::Division => {
+            if right == BigInt::ZERO {
+                return Err(EvalError::DivisionByZero);
+            }
+            allocate_integer(meter, left.bits())?;
+            Ok(Object::from(left / right))
+        }
         InfixOperator::Equal => Ok(Object::Bool(left == right)),
         InfixOperator::NotEqual => Ok(Object::Bool(left != right)),
         InfixOperator::GreaterThan => Ok(Object::Bool(left > right)),
         InfixOperator::LessThan => Ok(Object::Bool(left < right)),
+        InfixOperator::BitAnd | InfixOperator::BitOr | InfixOperator::BitXor => {
+            allocate_integer(meter, widest + 1)?;
+            Ok(Object::from(match operator {
+                InfixOperator::BitAnd => left & right,
+                InfixOperator::BitOr => left | right,
+                _ => left ^ right,
+            }))
+        }
+        InfixOperator::ShiftLeft => {
+            let amount = big_shift_amount(right)?;
+            if left == BigInt::ZERO {
+                return Ok(Object::Integer(0));
+            }
+            // Rejected before shifting, like pow
+            if left.bits().saturating_add(amount) > MAX_INTEGER_BITS {
+                return Err(integer_too_large());
+            }
+            meter.steps(amount / 64)?;
+            allocate_integer(meter, left.bits() + amount)?;
+            big_integer(left << amount)
+        }
+        InfixOperator::ShiftRight => {
+            let amount = big_shift_amount(right)?;
+            allocate_integer(meter, left.bits().saturating_sub(amount) + 1)?;
+            Ok(Object::from(left >> amount))
+        }
+    }
+}
+
+// Follows IEEE 754, dividing by zero gives inf or NaN instead of an error
+fn eval_expr_infix_float(
+    operator: InfixOperator,
+    left: f64,
+    right: f64,
+) -> Result<Object, EvalError> {
+    match operator {
+        InfixOperator::Plus => Ok(Object::Float(left + right)),
+        InfixOperator::Minus => Ok(Object::Float(left - right)),
+        InfixOperator::Mult => Ok(Object::Float(left * right)),
+        InfixOperator::Division => Ok(Object::Float(left / right)),
+        InfixOperator::Equal => Ok(Object::Bool(left == right)),
+        InfixOperator::NotEqual => Ok(Object::Bool(left != right)),
+        InfixOperator::GreaterThan => Ok(Object::Bool(left > right)),
+        InfixOperator::LessThan => Ok(Object::Bool(left < right)),
+        _ => unreachable!("bitwise operators are not defined on floats"),
+    }
+}
+
+// Any non-negative amount shifts the same whether the value is an i64 or was
+// promoted, left shifts are only bounded by MAX_INTEGER_BITS
+fn shift_amount(amount: i64) -> Result<u64, EvalError> {
+    u64::try_from(amount).map_err(|_| EvalError::InvalidShiftAmount(Object::Integer(amount)))
+}
+
+// Amounts beyond u64 shift every bit out just the same
+fn big_shift_amount(amount: BigInt) -> Result<u64, EvalError> {
+    if amount.is_negative() {
+        return Err(EvalError::InvalidShiftAmount(Object::from(amount)));
+    }
+    Ok(amount.to_u64().unwrap_or(u64::MAX))
+}
+
+// Integers with more bits are an error rather than taking unbounded time and
+// memory to compute
+const MAX_INTEGER_BITS: u64 = 1 << 20;
+
+fn big_integer(value: BigInt) -> Result<Object, EvalError> {
+    if value.bits() > MAX_INTEGER_BITS {
+        return Err(integer_too_large());
+    }
+    Ok(Object::from(value))
+}
+
+// Counts the memory of an integer of up to this many bits before making it,
+// as for strings
+fn allocate_integer(meter: Meter, bits: u64) -> Result<(), EvalError> {
+    meter.allocate(usize::try_from(bits.div_ceil(8)).unwrap_or(usize::MAX))
+}
+
+fn integer_too_large() -> EvalError {
+    EvalError::IntegerTooLarge {
+        bits: MAX_INTEGER_BITS,
+    }
+}
+
+fn to_big_integer(obj: Object) -> BigInt {
+    match obj {
+        Object::Integer(i) => BigInt::from(i),
+        Object::BigInt(i) => i,
+        _ => unreachable!("to_big_integer() called on a non integer object"),
     }
 }
 
@@ -253,49 +704,68 @@ fn is_true(condition: Object) -> bool {
     }
 }
 
-fn apply_function(obj_fn: Object, args: Vec<Object>) -> Result<Object, EvalError> {
-    let Object::Function {
-        body, parameters, ..
-    } = obj_fn.clone()
-    else {
-        return Err(EvalError::MismatchedObject {
-            expected: "Object::Function{ }".to_string(),
-            got: obj_fn,
-        });
-    };
-
-    if parameters.len() != args.len() {
-        return Err(EvalError::InvalidNumberArguments {
-            expected: parameters.len() as u64,
-            got: args.len() as u64,
-        });
-    }
-
-    let extended_env = extend_function_env(obj_fn, args)?;
-
-    let obj = Statement::Block(body).eval(extended_env)?;
-    Ok(match obj {
-        Object::ReturnValue { value } => *value,
-        _ => obj,
-    })
-}
+// Calls in tail position come back from the body as Flow::TailCall and are
+// made by the next iteration of the loop
+fn apply_function(
+    mut obj_fn: Object,
+    mut args: Vec<Object>,
+    mut call_site: Span,
+) -> Result<Object, EvalError> {
+    loop {
+        if let Object::Builtin(builtin) = obj_fn {
+            let frame = Frame {
+                function: Some(builtin.to_string()),
+                call_site,
+            };
+            return trace::with_frame(frame, || builtin.call(args));
+        }
 
-fn extend_function_env(obj_fn: Object, args: Vec<Object>) -> Result<Rc<Environment>, EvalError> {
-    let Object::Function {
-        parameters, env, ..
-    } = obj_fn
-    else {
-        return Err(EvalError::MismatchedObject {
-            expected: "Object::Function{ }".to_string(),
-            got: obj_fn,
-        });
-    };
+        let Object::Function {
+            name,
+            body,
+            parameters,
+            env,
+        } = obj_fn
+        else {
+            return Err(EvalError::MismatchedObject {
+                expected: "FUNCTION".to_string(),
+                got: obj_fn,
+            });
+        };
+
+        if parameters.len() != args.len() {
+            return Err(EvalError::InvalidNumberArguments {
+                expected: parameters.len() as u64,
+                got: args.len() as u64,
+            });
+        }
 
-    let env = Environment::with_outer(Rc::new(env));
+        limits::allocate(
+            std::mem::size_of::<Environment>() + parameters.len() * std::mem::size_of::<Object>(),
+        )?;
+        let extended_env = Environment::with_outer(env);
+        for (param, arg) in parameters.into_iter().zip(args) {
+            extended_env.set(param, arg);
+        }
 
-    for (param, arg) in parameters.into_iter().zip(args) {
-        env.set(param, arg);
+        let frame = Frame {
+            function: name,
+            call_site,
+        };
+        let flow = limits::with_call_depth(|| {
+            trace::with_frame(frame, || tail::eval_block(body, extended_env, true))
+        })?;
+        match flow {
+            Flow::Value(value) | Flow::Return(value) => return Ok(value),
+            Flow::TailCall {
+                function,
+                arguments,
+                span,
+            } => {
+                obj_fn = function;
+                args = arguments;
+                call_site = span;
+            }
+        }
     }
-
-    Ok(env)
 }
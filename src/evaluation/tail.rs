@@ -0,0 +1,126 @@
+use super::{limits, Eval, EvalError};
+use crate::{
+    ast::{Expression, Statement},
+    evaluation::{environment::Environment, eval_arguments, is_return, is_true},
+    object::Object,
+    token::Span,
+};
+use std::rc::Rc;
+
+// How evaluating part of a function body ended. Calls in tail position are
+// not made here but handed back to apply_function, which runs them in a loop
+// instead of nesting Rust calls, so that tail recursion of any depth runs in
+// constant stack space
+pub(super) enum Flow {
+    // The value of the last statement
+    Value(Object),
+    // A return statement ran
+    Return(Object),
+    TailCall {
+        function: Object,
+        arguments: Vec<Object>,
+        span: Span,
+    },
+}
+
+// A call is in tail position when it is returned, or is the last statement of
+// the body, possibly through the last statement of an if branch or a block.
+// With tail false only return statements are in tail position
+pub(super) fn eval_block(
+    statements: Vec<Statement>,
+    environment: Rc<Environment>,
+    tail: bool,
+) -> Result<Flow, EvalError> {
+    let mut flow = Flow::Value(Object::Null);
+    let last = statements.len().saturating_sub(1);
+
+    for (i, statement) in statements.into_iter().enumerate() {
+        flow = eval_statement(statement, environment.clone(), tail && i == last)?;
+        if !matches!(flow, Flow::Value(_)) {
+            break;
+        }
+    }
+
+    Ok(flow)
+}
+
+// Like Eval, statements and expressions evaluated here count as one step
+fn eval_statement(
+    statement: Statement,
+    environment: Rc<Environment>,
+    tail: bool,
+) -> Result<Flow, EvalError> {
+    match statement {
+        Statement::Return(expression) => {
+            limits::step()?;
+            Ok(match eval_expression(expression, environment, true)? {
+                Flow::Value(value) => Flow::Return(value),
+                flow => flow,
+            })
+        }
+        Statement::Expression(expression) => {
+            limits::step()?;
+            eval_expression(expression, environment, tail)
+        }
+        Statement::Block(statements) => {
+            limits::step()?;
+            eval_block(statements, environment, tail)
+        }
+        statement => value(statement.eval(environment)),
+    }
+}
+
+fn eval_expression(
+    expression: Expression,
+    environment: Rc<Environment>,
+    tail: bool,
+) -> Result<Flow, EvalError> {
+    match expression {
+        Expression::FunctionCall {
+            function,
+            arguments,
+            span,
+        } if tail => {
+            limits::step()?;
+            let function = function.eval(environment.clone())?;
+            if is_return(&function) {
+                return value(Ok(function));
+            }
+            let arguments = match eval_arguments(arguments, &environment)? {
+                Ok(arguments) => arguments,
+                Err(returned) => return value(Ok(returned)),
+            };
+            Ok(Flow::TailCall {
+                function,
+                arguments,
+                span: span.0,
+            })
+        }
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+        } => {
+            limits::step()?;
+            let condition = condition.eval(environment.clone())?;
+            if is_return(&condition) {
+                return value(Ok(condition));
+            }
+            if is_true(condition) {
+                eval_block(consequence, environment, tail)
+            } else if let Some(alternative) = alternative {
+                eval_block(alternative, environment, tail)
+            } else {
+                Ok(Flow::Value(Object::Null))
+            }
+        }
+        expression => value(expression.eval(environment)),
+    }
+}
+
+fn value(result: Result<Object, EvalError>) -> Result<Flow, EvalError> {
+    Ok(match result? {
+        Object::ReturnValue { value } => Flow::Return(*value),
+        value => Flow::Value(value),
+    })
+}
@@ -1,4 +1,5 @@
-use crate::{lexer::Lexer, parser::Parser};
+use crate::{lexer::Lexer, parser::Parser, Interrupt, Limits};
+use std::time::Duration;
 
 use super::*;
 
@@ -20,6 +21,8 @@ fn int_expression() {
         ("3 * 3 * 3 + 10", 37),
         ("3 * (3 * 3) + 10", 37),
         ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50),
+        ("0xFF + 0b1", 256),
+        ("1_000 * 0o10", 8000),
     ];
 
     for (input, expected) in tests {
@@ -28,13 +31,282 @@ fn int_expression() {
     }
 }
 
+#[test]
+fn big_int_expression() {
+    let tests = vec![
+        ("9223372036854775807 + 1", "9223372036854775808"),
+        ("-9223372036854775808 - 1", "-9223372036854775809"),
+        ("4294967296 * 4294967296", "18446744073709551616"),
+        ("-(-9223372036854775808)", "9223372036854775808"),
+        (
+            "99999999999999999999 * 99999999999999999999",
+            "9999999999999999999800000000000000000001",
+        ),
+        (
+            "123456789012345678901234567890 / 10",
+            "12345678901234567890123456789",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input.to_string());
+        test_big_integer_object(evaluated.unwrap(), expected);
+    }
+}
+
+#[test]
+fn big_int_demotion() {
+    let tests = vec![
+        ("9223372036854775808 - 1", i64::MAX),
+        ("-9223372036854775808", i64::MIN),
+        ("(9223372036854775807 + 10) - 10", i64::MAX),
+        ("99999999999999999999 / 99999999999999999999", 1),
+        ("99999999999999999999 - 99999999999999999999", 0),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input.to_string());
+        test_integer_object(evaluated.unwrap(), expected);
+    }
+}
+
+#[test]
+fn big_int_comparison() {
+    let tests = vec![
+        ("99999999999999999999 > 5", true),
+        ("99999999999999999999 < 5", false),
+        ("-99999999999999999999 < -9223372036854775808", true),
+        ("99999999999999999999 == 99999999999999999999", true),
+        ("99999999999999999999 != 99999999999999999998", true),
+        ("9223372036854775807 + 1 == 9223372036854775808", true),
+        ("!99999999999999999999", false),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input.to_string());
+        test_boolean_object(evaluated.unwrap(), expected);
+    }
+}
+
+fn test_big_integer_object(obj: Object, expected: &str) {
+    match obj {
+        Object::BigInt(value) => {
+            assert_eq!(value.to_string(), expected);
+        }
+        _ => {
+            panic!("Object is not BigInt but {}", obj);
+        }
+    }
+}
+
+#[test]
+fn float_expression() {
+    let tests = vec![
+        ("2.5", 2.5),
+        ("-2.5", -2.5),
+        ("1e-9", 1e-9),
+        ("0.5 + 0.25", 0.75),
+        ("1.5 * 2", 3.0),
+        ("3 / 2.0", 1.5),
+        ("2 - 0.5", 1.5),
+        ("99999999999999999999 * 1.0", 1e20),
+        ("(1 + 2) * 0.5", 1.5),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input.to_string());
+        test_float_object(evaluated.unwrap(), expected);
+    }
+}
+
+#[test]
+fn float_comparison() {
+    let tests = vec![
+        ("1.5 > 1", true),
+        ("1 < 1.5", true),
+        ("2.0 == 2", true),
+        ("0.1 + 0.2 == 0.3", false),
+        ("!0.0", true),
+        ("!1.5", false),
+        ("0.0 / 0.0 == 0.0 / 0.0", false),
+        ("0.0 / 0.0 != 0.0 / 0.0", true),
+        ("1.0 / 0.0 > 99999999999999999999", true),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input.to_string());
+        test_boolean_object(evaluated.unwrap(), expected);
+    }
+}
+
+#[test]
+fn float_special_values() {
+    let evaluated = test_eval("1 / 0.0".to_string()).unwrap();
+    assert_eq!(evaluated, Object::Float(f64::INFINITY));
+    assert_eq!(evaluated.to_string(), "inf");
+
+    let evaluated = test_eval("-1.0 / 0".to_string()).unwrap();
+    assert_eq!(evaluated, Object::Float(f64::NEG_INFINITY));
+
+    let evaluated = test_eval("0.0 / 0".to_string()).unwrap();
+    assert_eq!(evaluated.to_string(), "NaN");
+
+    let evaluated = test_eval("2.0 * 1.0".to_string()).unwrap();
+    assert_eq!(evaluated.to_string(), "2.0");
+}
+
+#[test]
+fn math_builtins() {
+    let tests = vec![
+        ("sqrt(16)", Object::Float(4.0)),
+        ("sqrt(2.25)", Object::Float(1.5)),
+        ("floor(2.7)", Object::Float(2.0)),
+        ("floor(-2.5)", Object::Float(-3.0)),
+        ("floor(7)", Object::Integer(7)),
+        ("ceil(2.1)", Object::Float(3.0)),
+        ("ceil(-2.1)", Object::Float(-2.0)),
+        ("round(2.5)", Object::Float(3.0)),
+        ("round(-2.5)", Object::Float(-3.0)),
+        ("round(2.4)", Object::Float(2.0)),
+        ("abs(-5)", Object::Integer(5)),
+        ("abs(-5.5)", Object::Float(5.5)),
+        (
+            "abs(-9223372036854775808)",
+            Object::BigInt("9223372036854775808".parse().unwrap()),
+        ),
+        ("pow(2, 10)", Object::Integer(1024)),
+        (
+            "pow(2, 64)",
+            Object::BigInt("18446744073709551616".parse().unwrap()),
+        ),
+        ("pow(2, -1)", Object::Float(0.5)),
+        ("pow(4, 0.5)", Object::Float(2.0)),
+        ("pow(1.5, 2)", Object::Float(2.25)),
+        ("pow(0.0, -1)", Object::Float(f64::INFINITY)),
+        (
+            "pow(2, 99999999999999999999 - 99999999999999999999)",
+            Object::Integer(1),
+        ),
+        ("pow(0, 0)", Object::Integer(1)),
+        ("pow(0, 99999999999999999999)", Object::Integer(0)),
+        ("pow(1, 4000000000)", Object::Integer(1)),
+        ("pow(-1, 99999999999999999999)", Object::Integer(-1)),
+        ("pow(-1, 99999999999999999998)", Object::Integer(1)),
+        ("pow(2.0, 4000000000)", Object::Float(f64::INFINITY)),
+        ("pow(10, 400) * 1.0", Object::Float(f64::INFINITY)),
+        ("-pow(10, 400) * 1.0", Object::Float(f64::NEG_INFINITY)),
+        ("floor(1.0 / 0.0)", Object::Float(f64::INFINITY)),
+        ("let sqrt = fn(x) { x }; sqrt(4)", Object::Integer(4)),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input.to_string());
+        assert_eq!(evaluated.unwrap(), expected, "{input}");
+    }
+
+    for input in ["sqrt(-1)", "floor(0.0 / 0.0)", "abs(0.0 / 0.0)"] {
+        match test_eval(input.to_string()).unwrap() {
+            Object::Float(x) => assert!(x.is_nan(), "{input}"),
+            obj => panic!("Object is not Float but {}", obj),
+        }
+    }
+}
+
+fn test_float_object(obj: Object, expected: f64) {
+    match obj {
+        Object::Float(value) => {
+            assert_eq!(value, expected);
+        }
+        _ => {
+            panic!("Object is not Float but {}", obj);
+        }
+    }
+}
+
+#[test]
+fn bitwise_expression() {
+    let tests = vec![
+        ("0b1100 & 0b1010", 0b1000),
+        ("0b1100 | 0b1010", 0b1110),
+        ("0b1100 ^ 0b1010", 0b0110),
+        ("~0", -1),
+        ("~5", -6),
+        ("1 << 10", 1024),
+        ("1024 >> 3", 128),
+        ("-16 >> 2", -4),
+        ("0o755 & 0o700", 0o700),
+        ("1 | 2 ^ 3 & 4", 3),
+        ("1 << 2 + 1", 8),
+        ("(1 << 62) >> 62", 1),
+        ("(1 << 63) >> 60", 8),
+        ("1 >> 64", 0),
+        ("-1 >> 99999999999999999999", -1),
+        ("(1 << 64) >> 64", 1),
+        ("0 << 99999999999999999999", 0),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input.to_string());
+        test_integer_object(evaluated.unwrap(), expected);
+    }
+
+    let evaluated = test_eval("~99999999999999999999 + 1 == -99999999999999999999".to_string());
+    test_boolean_object(evaluated.unwrap(), true);
+
+    let evaluated = test_eval("1 << 63".to_string());
+    test_big_integer_object(evaluated.unwrap(), "9223372036854775808");
+
+    // The same whether the value was an i64 or already promoted
+    let evaluated = test_eval("1 << 64".to_string());
+    test_big_integer_object(evaluated.unwrap(), "18446744073709551616");
+    let evaluated = test_eval("(1 << 63) << 1".to_string());
+    test_big_integer_object(evaluated.unwrap(), "18446744073709551616");
+
+    let evaluated = test_eval("0xFFFFFFFFFFFFFFFFFF & 0xF0".to_string());
+    test_integer_object(evaluated.unwrap(), 0xF0);
+}
+
+#[test]
+fn string_expression() {
+    let tests = vec![
+        (r#""hello""#, Object::Str("hello".into())),
+        (r#"let s = "a\tb"; s"#, Object::Str("a\tb".into())),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input.to_string());
+        assert_eq!(evaluated.unwrap(), expected, "{input}");
+    }
+}
+
+// Binding, reading and passing a string share it rather than copying it
+#[test]
+fn shared_strings() {
+    let env = Environment::new_rc();
+    let input = r#"let s = "abc"; let t = s; let id = fn(x) { x }; let u = id(t); u"#;
+    let program = Parser::new(Lexer::new(input.to_string())).parse_program();
+    let result = program
+        .unwrap()
+        .eval(env.clone(), &Limits::default())
+        .unwrap();
+
+    match (env.get("s"), result) {
+        (Some(Object::Str(s)), Object::Str(u)) => assert!(Rc::ptr_eq(&s, &u)),
+        objects => panic!("expected strings, got {:?}", objects),
+    }
+}
+
 fn test_eval(input: String) -> Result<Object, EvalError> {
+    test_eval_with(&Limits::default(), input)
+}
+
+fn test_eval_with(limits: &Limits, input: String) -> Result<Object, EvalError> {
     let lexer = Lexer::new(input);
     let mut parser = Parser::new(lexer);
     let program = parser.parse_program().expect("parse_program() failed");
     let env = Environment::new_rc();
 
-    program.eval(env)
+    program.eval(env, limits).map_err(|err| err.error)
 }
 
 fn test_integer_object(obj: Object, expected: i64) {
@@ -213,6 +485,86 @@ fn error_handling() {
             "foobar",
             EvalError::IdentifierNotFound("foobar".to_string()),
         ),
+        ("5 / 0", EvalError::DivisionByZero),
+        (
+            r#""a" + "b""#,
+            EvalError::UnknownInfix {
+                left: Object::Str("a".into()),
+                operator: InfixOperator::Plus,
+                right: Object::Str("b".into()),
+            },
+        ),
+        (
+            r#""a" == "a""#,
+            EvalError::UnknownInfix {
+                left: Object::Str("a".into()),
+                operator: InfixOperator::Equal,
+                right: Object::Str("a".into()),
+            },
+        ),
+        (
+            r#""a" + 1"#,
+            EvalError::MismatchedTypes {
+                left: Object::Str("a".into()),
+                operator: InfixOperator::Plus,
+                right: Object::Integer(1),
+            },
+        ),
+        (
+            "1 << -1",
+            EvalError::InvalidShiftAmount(Object::Integer(-1)),
+        ),
+        (
+            "1 << -99999999999999999999",
+            EvalError::InvalidShiftAmount(Object::BigInt("-99999999999999999999".parse().unwrap())),
+        ),
+        (
+            "1 << 99999999999999999999",
+            EvalError::IntegerTooLarge { bits: 1 << 20 },
+        ),
+        ("1 << 1048576", EvalError::IntegerTooLarge { bits: 1 << 20 }),
+        (
+            "1.5 & 1",
+            EvalError::MismatchedTypes {
+                left: Object::Float(1.5),
+                operator: InfixOperator::BitAnd,
+                right: Object::Integer(1),
+            },
+        ),
+        (
+            "~1.5",
+            EvalError::UnknownPrefix {
+                operator: PrefixOperator::BitNot,
+                value: Object::Float(1.5),
+            },
+        ),
+        (
+            "sqrt(true)",
+            EvalError::MismatchedObject {
+                expected: "INTEGER or FLOAT".to_string(),
+                got: Object::Bool(true),
+            },
+        ),
+        (
+            "pow(2)",
+            EvalError::InvalidNumberArguments {
+                expected: 2,
+                got: 1,
+            },
+        ),
+        ("99999999999999999999 / 0", EvalError::DivisionByZero),
+        (
+            "pow(2, 4000000000)",
+            EvalError::IntegerTooLarge { bits: 1 << 20 },
+        ),
+        (
+            "pow(2, 99999999999999999999)",
+            EvalError::IntegerTooLarge { bits: 1 << 20 },
+        ),
+        (
+            "pow(3, 700000)",
+            EvalError::IntegerTooLarge { bits: 1 << 20 },
+        ),
     ];
 
     for (input, expected) in tests {
@@ -239,6 +591,8 @@ fn let_statement() {
         ("let a = 5 * 5; a;", 25),
         ("let a = 5; let b = a; b;", 5),
         ("let a = 5; let b = a; let c = a + b + 5; c;", 15),
+        ("let x1 = 5; let x2 = x1 * 2; x2;", 10),
+        ("let größe = 3; let 長さ = größe + 1; 長さ;", 4),
     ];
 
     for (input, expected) in tests {
@@ -295,3 +649,906 @@ fn closure() {
     let evaluated = test_eval(input.to_string());
     test_integer_object(evaluated.unwrap(), 5);
 }
+
+#[test]
+fn closure_environment() {
+    let tests = vec![
+        // Bindings made after the function are visible when it runs
+        (
+            "let get = fn() { count }; let count = 1; let a = get(); let count = 2; a + get()",
+            3,
+        ),
+        // Counters that hand out the next counter
+        (
+            r#"
+            let counter = fn(n) {
+                fn(step) { if (step == 0) { n } else { counter(n + step) } }
+            };
+            counter(0)(1)(2)(3)(0)
+            "#,
+            6,
+        ),
+        (
+            "let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } }; fact(10)",
+            3628800,
+        ),
+        (
+            r#"
+            let isEven = fn(n) { if (n == 0) { true } else { isOdd(n - 1) } };
+            let isOdd = fn(n) { if (n == 0) { false } else { isEven(n - 1) } };
+            if (isEven(10)) { if (isOdd(7)) { 1 } else { 2 } } else { 3 }
+            "#,
+            1,
+        ),
+        // Closures returned from closures
+        (
+            "let add = fn(a) { fn(b) { fn(c) { a + b + c } } }; add(1)(2)(3)",
+            6,
+        ),
+        (
+            r#"
+            let compose = fn(f, g) { fn(x) { g(f(x)) } };
+            let inc = fn(x) { x + 1 };
+            let double = fn(x) { x * 2 };
+            compose(inc, compose(double, inc))(5)
+            "#,
+            13,
+        ),
+        // Parameters shadow outer bindings, the call environment is dropped
+        // after the call
+        ("let x = 1; let f = fn(x) { let y = x; y }; f(5) + x", 6),
+        ("let y = 1; let f = fn() { let y = 10; y }; f() + y", 11),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input.to_string());
+        test_integer_object(evaluated.unwrap(), expected);
+    }
+}
+
+#[test]
+fn null_binding() {
+    let evaluated = test_eval("let x = if (false) { 1 }; x".to_string());
+    test_null_object(evaluated.unwrap());
+
+    test_error_object(
+        test_eval("let f = fn() { x }; f()".to_string()),
+        EvalError::IdentifierNotFound("x".to_string()),
+    );
+}
+
+// A function bound in the environment it captured is a cycle, comparing and
+// printing it must not follow it
+#[test]
+fn recursive_function_obj() {
+    let input = "let f = fn() { f }; f()";
+    let evaluated = test_eval(input.to_string()).unwrap();
+
+    assert_eq!(evaluated, evaluated.clone());
+    assert!(format!("{:?}", evaluated).contains("names: [\"f\"]"));
+}
+
+// Recursive functions are bound in the environment they capture, which is a
+// reference cycle
+#[test]
+fn cycle_collection() {
+    Environment::collect_cycles();
+    let baseline = Environment::live_count();
+
+    // Dropping the environment of a session
+    for _ in 0..100 {
+        test_eval("let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(10)".to_string())
+            .unwrap();
+    }
+    assert!(Environment::live_count() > baseline);
+    Environment::collect_cycles();
+    assert_eq!(Environment::live_count(), baseline);
+
+    // Calls leaving a cycle behind in a long running session
+    let env = Environment::new_rc();
+    let define = "let g = fn() { let h = fn() { h }; h };";
+    let program = Parser::new(Lexer::new(define.to_string())).parse_program();
+    program
+        .unwrap()
+        .eval(env.clone(), &Limits::default())
+        .unwrap();
+
+    let mut counts = vec![];
+    for _ in 0..5 {
+        for _ in 0..2000 {
+            let program = Parser::new(Lexer::new("g()".to_string())).parse_program();
+            program
+                .unwrap()
+                .eval(env.clone(), &Limits::default())
+                .unwrap();
+        }
+        counts.push(Environment::live_count());
+    }
+    // Bounded by the collections made while evaluating
+    assert!(
+        counts.iter().all(|count| *count < baseline + 1000),
+        "{:?}",
+        counts
+    );
+
+    // Environments still in use are kept
+    let program = Parser::new(Lexer::new("let h = g(); h".to_string())).parse_program();
+    let h = program
+        .unwrap()
+        .eval(env.clone(), &Limits::default())
+        .unwrap();
+    Environment::collect_cycles();
+    assert_eq!(Environment::live_count(), baseline + 2);
+    assert_eq!(
+        h,
+        apply_function(h.clone(), vec![], Span::default()).unwrap()
+    );
+
+    drop(env);
+    drop(h);
+    Environment::collect_cycles();
+    assert_eq!(Environment::live_count(), baseline);
+}
+
+// Tail calls run in constant stack space, on the 2 MiB stack of a test thread.
+// Without them, debug builds overflow it after about a hundred calls
+#[test]
+fn tail_calls() {
+    let tests = vec![
+        (
+            "let count = fn(n, acc) { if (n == 0) { acc } else { count(n - 1, acc + 1) } }; count(1000000, 0)",
+            1000000,
+        ),
+        (
+            "let count = fn(n) { if (n == 0) { return 0; } return count(n - 1); }; count(100000)",
+            0,
+        ),
+        (
+            r#"
+            let isEven = fn(n) { if (n == 0) { true } else { isOdd(n - 1) } };
+            let isOdd = fn(n) { if (n == 0) { false } else { return isEven(n - 1); } };
+            if (isEven(100000)) { 1 } else { 0 }
+            "#,
+            1,
+        ),
+        // Through blocks and lets before the call, and through a builtin
+        (
+            "let f = fn(n) { let m = n - 1; if (m < 0) { abs(-7) } else { f(m) } }; f(100000)",
+            7,
+        ),
+        // Calls not in tail position still return to their caller
+        (
+            "let sum = fn(n) { if (n == 0) { 0 } else { n + sum(n - 1) } }; sum(30)",
+            465,
+        ),
+        (
+            "let f = fn(n) { if (n > 0) { f(0); } n + 1 }; f(5)",
+            6,
+        ),
+        ("let f = fn() { let g = fn(x) { x * 2 }; return g(21); 0 }; f()", 42),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input.to_string());
+        test_integer_object(evaluated.unwrap(), expected);
+    }
+
+    test_error_object(
+        test_eval("let f = fn(n) { if (n == 0) { g(1, 2) } else { f(n - 1) } }; let g = fn(x) { x }; f(10)".to_string()),
+        EvalError::InvalidNumberArguments {
+            expected: 1,
+            got: 2,
+        },
+    );
+}
+
+#[test]
+fn call_depth_limit() {
+    let overflow = EvalError::StackOverflow {
+        depth: Limits::default().max_call_depth,
+    };
+    // Deeper than the Rust stack of a test thread allows without growing it
+    test_error_object(
+        test_eval("let f = fn(n) { 1 + f(n + 1) }; f(0)".to_string()),
+        overflow.clone(),
+    );
+    test_error_object(
+        test_eval("let f = fn(n) { let g = fn() { f(n) }; g() + 1 }; f(0)".to_string()),
+        overflow,
+    );
+
+    let limits = Limits {
+        max_call_depth: 100,
+        ..Default::default()
+    };
+    let sum = "let sum = fn(n) { if (n == 0) { 0 } else { n + sum(n - 1) } };";
+    test_integer_object(
+        test_eval_with(&limits, format!("{} sum(99)", sum)).unwrap(),
+        4950,
+    );
+    test_error_object(
+        test_eval_with(&limits, format!("{} sum(100)", sum)),
+        EvalError::StackOverflow { depth: 100 },
+    );
+    // The depth is back to 0 after an error
+    test_integer_object(
+        test_eval_with(&limits, format!("{} sum(99)", sum)).unwrap(),
+        4950,
+    );
+    // Tail calls do not count
+    test_integer_object(
+        test_eval_with(
+            &limits,
+            "let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(10000)".to_string(),
+        )
+        .unwrap(),
+        0,
+    );
+}
+
+#[test]
+fn step_limit() {
+    let limits = Limits {
+        max_steps: Some(10),
+        ..Default::default()
+    };
+
+    // A let statement, the infix and its two operands, an expression
+    // statement and its identifier
+    test_integer_object(
+        test_eval_with(&limits, "let a = 1 + 2; a".to_string()).unwrap(),
+        3,
+    );
+    test_error_object(
+        test_eval_with(&limits, "let a = 1 + 2; let b = a * a; b + 1".to_string()),
+        EvalError::StepLimit { steps: 10 },
+    );
+    // Counted again for each program
+    test_integer_object(
+        test_eval_with(&limits, "let a = 1 + 2; a".to_string()).unwrap(),
+        3,
+    );
+
+    let limits = Limits {
+        max_steps: Some(100_000),
+        ..Default::default()
+    };
+    test_error_object(
+        test_eval_with(&limits, "let f = fn() { f() }; f()".to_string()),
+        EvalError::StepLimit { steps: 100_000 },
+    );
+}
+
+#[test]
+fn timeout() {
+    let timeout = Duration::from_millis(50);
+    let limits = Limits {
+        timeout: Some(timeout),
+        ..Default::default()
+    };
+
+    test_error_object(
+        test_eval_with(&limits, "let f = fn(n) { f(n + 1) }; f(0)".to_string()),
+        EvalError::Timeout(timeout),
+    );
+    test_integer_object(test_eval_with(&limits, "1 + 1".to_string()).unwrap(), 2);
+}
+
+#[test]
+fn interrupt() {
+    let interrupt = Interrupt::new();
+    let limits = Limits {
+        interrupt: Some(interrupt.clone()),
+        ..Default::default()
+    };
+
+    let handle = interrupt.clone();
+    let host = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        handle.interrupt();
+    });
+    test_error_object(
+        test_eval_with(&limits, "let f = fn(n) { f(n + 1) }; f(0)".to_string()),
+        EvalError::Interrupted,
+    );
+    host.join().unwrap();
+
+    // The handle stays interrupted until the host resets it
+    assert!(interrupt.is_interrupted());
+    test_error_object(
+        test_eval_with(&limits, "1 + 1".to_string()),
+        EvalError::Interrupted,
+    );
+    interrupt.reset();
+    test_integer_object(test_eval_with(&limits, "1 + 1".to_string()).unwrap(), 2);
+
+    // An interrupt made just before an evaluation starts stops it
+    interrupt.interrupt();
+    test_error_object(
+        test_eval_with(&limits, "let f = fn(n) { f(n + 1) }; f(0)".to_string()),
+        EvalError::Interrupted,
+    );
+}
+
+// A single big integer operation cannot outlast the timeout, its size is
+// bounded and it counts as many steps
+#[test]
+fn big_integer_limits() {
+    let timeout = Duration::from_millis(500);
+    let limits = Limits {
+        timeout: Some(timeout),
+        ..Default::default()
+    };
+
+    let square = "let sq = fn(x, n) { if (n == 0) { x } else { sq(x * x, n - 1) } };";
+    let tests = vec![
+        "pow(2, 4000000000)".to_string(),
+        "1 << 4000000000".to_string(),
+        format!("{} sq(3, 40)", square),
+    ];
+    for input in tests {
+        let start = std::time::Instant::now();
+        assert!(test_eval_with(&limits, input.clone()).is_err(), "{}", input);
+        assert!(
+            start.elapsed() < timeout,
+            "{}: {:?}",
+            input,
+            start.elapsed()
+        );
+    }
+
+    let limits = Limits {
+        max_steps: Some(1000),
+        ..Default::default()
+    };
+    test_error_object(
+        test_eval_with(&limits, format!("{} sq(3, 17)", square)),
+        EvalError::StepLimit { steps: 1000 },
+    );
+    test_error_object(
+        test_eval_with(&limits, "pow(3, 100000)".to_string()),
+        EvalError::StepLimit { steps: 1000 },
+    );
+
+    // They are counted as allocated before being made
+    let limit = 100_000;
+    let limits = Limits {
+        max_allocated: Some(limit),
+        ..Default::default()
+    };
+    let tests = vec![
+        format!("{} sq(3, 40)", square),
+        "pow(3, 600000)".to_string(),
+        "1 << 1000000".to_string(),
+        "pow(2, 300000) * pow(2, 300000)".to_string(),
+    ];
+    for input in tests {
+        test_error_object(
+            test_eval_with(&limits, input),
+            EvalError::OutOfMemory { limit },
+        );
+    }
+
+    let limits = Limits {
+        max_steps: Some(10_000),
+        max_allocated: Some(limit),
+        ..Default::default()
+    };
+    let start = std::time::Instant::now();
+    assert!(test_eval_with(&limits, format!("{} sq(3, 40)", square)).is_err());
+    assert!(start.elapsed() < timeout, "{:?}", start.elapsed());
+}
+
+// Without arrays, scripts grow memory with strings and with environments, such
+// as lists made of closures
+#[test]
+fn allocation_limit() {
+    let limit = 1024 * 1024;
+    let limits = Limits {
+        max_allocated: Some(limit),
+        ..Default::default()
+    };
+
+    let tests = vec![
+        // A string literal evaluated again and again, each time a new string
+        r#"let f = fn(n, s) { f(n + 1, "memory") }; f(0, "")"#,
+        // A list of a million closures
+        "let build = fn(n, list) { if (n == 0) { list } else { build(n - 1, fn() { list }) } }; build(1000000, 0)",
+        "let f = fn(n) { let a = n; let b = n; f(n + 1) }; f(0)",
+        // Results and errors, made again and again
+        "let f = fn(n, r) { f(n + 1, is_ok(ok(n))) }; f(0, true)",
+        r#"let f = fn(n, e) { f(n + 1, error("a", "b")) }; f(0, 0)"#,
+    ];
+    for input in tests {
+        test_error_object(
+            test_eval_with(&limits, input.to_string()),
+            EvalError::OutOfMemory { limit },
+        );
+    }
+
+    // Counted again for each program
+    test_integer_object(
+        test_eval_with(&limits, r#"let s = "ab"; let t = s; 1"#.to_string()).unwrap(),
+        1,
+    );
+}
+
+#[test]
+fn stack_trace() {
+    let tests = vec![
+        (
+            "let div = fn(a, b) { a / b };\nlet half = fn(x) { 1 + div(x, 0) };\nhalf(4)",
+            "Traceback (most recent call last):\n  at 3:1-3:8, in half\n  at 2:24-2:33, in div\nerror[E0008]: division by zero",
+        ),
+        // Builtins have a frame, functions not bound with let have none
+        (
+            "fn(x) { 1 + sqrt(x) }(\"a\")",
+            "Traceback (most recent call last):\n  at 1:1-1:27, in <anonymous>\n  at 1:13-1:20, in sqrt\nerror[E0006]: expected INTEGER or FLOAT, got STRING",
+        ),
+        // Tail calls replace the frame of their caller
+        (
+            "let f = fn(n) { if (n == 0) { g() } else { f(n - 1) } };\nlet g = fn() { 1 + h() };\nlet h = fn() { x };\nf(3)",
+            "Traceback (most recent call last):\n  at 1:31-1:34, in g\n  at 2:20-2:23, in h\nerror[E0005]: identifier not found: x",
+        ),
+        (
+            "let f = fn(n) { if (n == 0) { 1 / 0 } else { 1 + f(n - 1) } }; f(3)",
+            "Traceback (most recent call last):\n  at 1:64-1:68, in f\n  at 1:50-1:58, in f\n  [Previous frame repeated 2 more times]\nerror[E0008]: division by zero",
+        ),
+        // Errors outside any call
+        ("let f = fn() { 1 }; f(1)", "error[E0007]: wrong number of arguments: expected 0, got 1"),
+    ];
+
+    for (input, expected) in tests {
+        let program = Parser::new(Lexer::new(input.to_string()))
+            .parse_program()
+            .unwrap();
+        match program.eval(Environment::new_rc(), &Limits::default()) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{}", input),
+            Ok(obj) => panic!("{}: expected an error, got {}", input, obj),
+        }
+    }
+}
+
+#[test]
+fn exceptions() {
+    let str = |str: &str| Object::Str(str.into());
+    let tests = vec![
+        ("try { 1 / 0 } catch (e) { kind(e) }", str("DivisionByZero")),
+        ("try { 1 / 0 } catch (e) { message(e) }", str("division by zero")),
+        (
+            "try { foo } catch (e) { e }",
+            Object::Error {
+                kind: "IdentifierNotFound".into(),
+                message: "identifier not found: foo".into(),
+                stack: vec![],
+            },
+        ),
+        (
+            "let f = fn() { 1 + true }; try { f() } catch (e) { message(e) }",
+            str("type mismatch: INTEGER + BOOLEAN"),
+        ),
+        (
+            "let f = fn(n) { 1 + f(n) }; try { f(0) } catch (e) { kind(e) }",
+            str("StackOverflow"),
+        ),
+        // Thrown values other than errors become errors of kind Error
+        ("try { throw \"oops\" } catch (e) { message(e) }", str("oops")),
+        ("try { throw 42 } catch (e) { kind(e) }", str("Error")),
+        ("try { throw 42 } catch (e) { message(e) }", str("42")),
+        (
+            "try { throw error(\"Parse\", \"bad digit\") } catch (e) { e }",
+            Object::Error {
+                kind: "Parse".into(),
+                message: "bad digit".into(),
+                stack: vec![],
+            },
+        ),
+        (
+            "try { try { throw \"inner\" } catch (e) { throw error(\"Outer\", message(e)) } } catch (e) { error(kind(e), message(e)) }",
+            Object::Error {
+                kind: "Outer".into(),
+                message: "inner".into(),
+                stack: vec![],
+            },
+        ),
+        // The value of the try or catch clause that ran
+        ("let x = try { 5 } catch (e) { 0 }; x", Object::Integer(5)),
+        ("let x = try { throw 5 } catch (e) { 0 }; x", Object::Integer(0)),
+        // The error stays bound after the catch clause, like a let in a block
+        ("try { throw 1 } catch (e) { 0 }; message(e)", str("1")),
+        // Outermost call first, one per line
+        (
+            "let f = fn() { throw \"x\" };\nlet g = fn() { f(); 1 };\ntry { g() } catch (e) { stack(e) }",
+            str("at 3:7-3:10, in g\nat 2:16-2:19, in f"),
+        ),
+        (
+            "let div = fn(a, b) { a / b };\ntry { div(1, 0) } catch (e) { stack(e) }",
+            str("at 2:7-2:16, in div"),
+        ),
+        ("try { 1 / 0 } catch (e) { stack(e) }", str("")),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input.to_string()).unwrap(), expected, "{}", input);
+    }
+
+    let tests = vec![
+        (
+            "kind(1)",
+            EvalError::MismatchedObject {
+                expected: "ERROR".to_string(),
+                got: Object::Integer(1),
+            },
+        ),
+        (
+            "error(\"Kind\", 1)",
+            EvalError::MismatchedObject {
+                expected: "STRING".to_string(),
+                got: Object::Integer(1),
+            },
+        ),
+        // Only the catch clause of an enclosing try applies
+        (
+            "try { 1 } catch (e) { 2 }; throw \"late\"",
+            EvalError::Thrown(Box::new(Object::Error {
+                kind: "Error".into(),
+                message: "late".into(),
+                stack: vec![],
+            })),
+        ),
+    ];
+    for (input, expected) in tests {
+        test_error_object(test_eval(input.to_string()), expected);
+    }
+}
+
+#[test]
+fn finally_clauses() {
+    let tests = vec![
+        ("try { 1 } finally { 2 }", Object::Integer(1)),
+        (
+            "try { try { throw \"a\" } finally { let ran = true; } } catch (e) { ran }",
+            Object::Bool(true),
+        ),
+        // A return goes on once the finally clause ran
+        (
+            "let f = fn() { try { return 1; } finally { let cleaned = true; } 2 }; f()",
+            Object::Integer(1),
+        ),
+        (
+            "let f = fn() { try { throw \"a\" } catch (e) { return message(e); } finally { 1 } 2 }; f()",
+            Object::Str("a".into()),
+        ),
+        ("try { return 7; } finally { 1 }; 8", Object::Integer(7)),
+        // A return in the finally clause replaces a value, a return or an error
+        (
+            "let f = fn() { try { return 1; } finally { return 2; } }; f()",
+            Object::Integer(2),
+        ),
+        (
+            "let f = fn() { try { 1 / 0 } finally { return 3; } }; f()",
+            Object::Integer(3),
+        ),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input.to_string()).unwrap(), expected, "{}", input);
+    }
+
+    let tests = vec![
+        ("try { 1 / 0 } finally { 5 }", EvalError::DivisionByZero),
+        (
+            "try { 1 / 0 } finally { throw \"cleanup\" }",
+            EvalError::Thrown(Box::new(Object::Error {
+                kind: "Error".into(),
+                message: "cleanup".into(),
+                stack: vec![],
+            })),
+        ),
+    ];
+    for (input, expected) in tests {
+        test_error_object(test_eval(input.to_string()), expected);
+    }
+}
+
+#[test]
+fn uncaught_exceptions() {
+    let tests = vec![
+        (
+            "let f = fn() { throw error(\"Parse\", \"bad digit\") };\nf()",
+            "Traceback (most recent call last):\n  at 2:1-2:4, in f\nerror[E0017]: Parse: bad digit",
+        ),
+        // Rethrowing keeps the stack of the first throw
+        (
+            "let f = fn() { throw \"x\" };\nlet g = fn(e) { throw e };\ntry { f() } catch (e) { g(e) }",
+            "Traceback (most recent call last):\n  at 3:7-3:10, in f\nerror[E0017]: Error: x",
+        ),
+        // The trace of an error in a finally clause is its own
+        (
+            "let f = fn() { 1 / 0 };\nlet g = fn() { x };\ntry { f() } finally { g() }",
+            "Traceback (most recent call last):\n  at 3:23-3:26, in g\nerror[E0005]: identifier not found: x",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let program = Parser::new(Lexer::new(input.to_string()))
+            .parse_program()
+            .unwrap();
+        match program.eval(Environment::new_rc(), &Limits::default()) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{}", input),
+            Ok(obj) => panic!("{}: expected an error, got {}", input, obj),
+        }
+    }
+}
+
+// The limits stop the program, catch and finally clauses included
+#[test]
+fn uncatchable_errors() {
+    let limits = Limits {
+        max_steps: Some(1000),
+        ..Default::default()
+    };
+    let tests = vec![
+        "let f = fn() { f() }; try { f() } catch (e) { 0 }",
+        "let f = fn() { f() }; let g = fn() { try { f() } finally { return 0; } }; g()",
+    ];
+    for input in tests {
+        test_error_object(
+            test_eval_with(&limits, input.to_string()),
+            EvalError::StepLimit { steps: 1000 },
+        );
+    }
+}
+
+#[test]
+fn results() {
+    let ok = |value| Object::Ok {
+        value: Box::new(value),
+    };
+    let err = |value| Object::Err {
+        value: Box::new(value),
+    };
+    let str = |str: &str| Object::Str(str.into());
+
+    let tests = vec![
+        ("ok(1)", ok(Object::Integer(1))),
+        ("err(\"bad\")", err(str("bad"))),
+        ("is_ok(ok(1))", Object::Bool(true)),
+        ("is_ok(err(1))", Object::Bool(false)),
+        ("is_err(err(1))", Object::Bool(true)),
+        ("unwrap(ok(2))", Object::Integer(2)),
+        ("unwrap_or(ok(1), 5)", Object::Integer(1)),
+        ("unwrap_or(err(1), 5)", Object::Integer(5)),
+        ("unwrap_err(err(\"bad\"))", str("bad")),
+        // Unwrapping an err throws its value
+        (
+            "try { unwrap(err(\"bad\")) } catch (e) { message(e) }",
+            str("bad"),
+        ),
+        (
+            "try { unwrap(err(error(\"Parse\", \"x\"))) } catch (e) { kind(e) }",
+            str("Parse"),
+        ),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input.to_string()).unwrap(), expected, "{}", input);
+    }
+
+    let tests = vec![
+        (
+            "is_ok(1)",
+            EvalError::MismatchedObject {
+                expected: "RESULT".to_string(),
+                got: Object::Integer(1),
+            },
+        ),
+        (
+            "unwrap_err(ok(1))",
+            EvalError::Custom("unwrap_err called on ok(1)".to_string()),
+        ),
+        // Nested a million times, were the depth not bounded
+        (
+            "let f = fn(n, r) { if (n == 0) { r } else { f(n - 1, ok(r)) } }; f(1000000, 1); 1",
+            EvalError::ResultTooDeep { depth: 100 },
+        ),
+    ];
+    for (input, expected) in tests {
+        test_error_object(test_eval(input.to_string()), expected);
+    }
+
+    test_integer_object(
+        test_eval(
+            "let f = fn(n, r) { if (n == 0) { r } else { f(n - 1, err(r)) } }; let r = f(99, 1); let s = r; 1"
+                .to_string(),
+        )
+        .unwrap(),
+        1,
+    );
+    test_integer_object(
+        test_eval(
+            "let f = fn(n, r) { try { f(n - 1, ok(r)) } catch (e) { n } }; f(1000000, 1)"
+                .to_string(),
+        )
+        .unwrap(),
+        999900,
+    );
+}
+
+#[test]
+fn propagate_operator() {
+    let ok = |value| Object::Ok {
+        value: Box::new(value),
+    };
+    let err = |value| Object::Err {
+        value: Box::new(value),
+    };
+    let half = "let half = fn(n) { if (n / 2 * 2 == n) { ok(n / 2) } else { err(\"odd\") } };";
+    let quarter = "let quarter = fn(n) { let h = half(n)?; ok(half(h)?) };";
+
+    let tests = vec![
+        (
+            format!("{} {} quarter(8)", half, quarter),
+            ok(Object::Integer(2)),
+        ),
+        (
+            format!("{} {} quarter(6)", half, quarter),
+            err(Object::Str("odd".into())),
+        ),
+        // The err is returned from anywhere in an expression
+        (
+            "let f = fn(r) { 1 + r? * 2 }; f(ok(3))".to_string(),
+            Object::Integer(7),
+        ),
+        (
+            "let f = fn(r) { 1 + r? * 2 }; f(err(0))".to_string(),
+            err(Object::Integer(0)),
+        ),
+        (
+            "let f = fn(r) { -r? }; f(err(0))".to_string(),
+            err(Object::Integer(0)),
+        ),
+        (
+            "let g = fn(x) { x + 1 }; let f = fn(r) { let x = g(r?); ok(x) }; f(err(0))"
+                .to_string(),
+            err(Object::Integer(0)),
+        ),
+        // Also from calls and ifs in tail position
+        (
+            "let g = fn(x) { x + 1 }; let f = fn(r) { g(r?) }; f(err(0))".to_string(),
+            err(Object::Integer(0)),
+        ),
+        (
+            "let f = fn(r) { if (r?) { 1 } else { 2 } }; f(err(0))".to_string(),
+            err(Object::Integer(0)),
+        ),
+        (
+            "let f = fn(r) { let x = if (r?) { 1 } else { 2 }; ok(x) }; f(ok(false))".to_string(),
+            ok(Object::Integer(2)),
+        ),
+        (
+            "let f = fn(r) { r?(1) }; f(ok(fn(x) { x * 10 }))".to_string(),
+            Object::Integer(10),
+        ),
+        (
+            "let f = fn(r) { return r?; }; f(err(0))".to_string(),
+            err(Object::Integer(0)),
+        ),
+        (
+            "let f = fn(r) { throw r?; }; f(err(0))".to_string(),
+            err(Object::Integer(0)),
+        ),
+        // Through finally clauses, and out of the program at the top level
+        (
+            "let f = fn(r) { try { r? } finally { let done = true; } 2 }; f(err(0))".to_string(),
+            err(Object::Integer(0)),
+        ),
+        ("err(0)?; 2".to_string(), err(Object::Integer(0))),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input.clone()).unwrap(), expected, "{}", input);
+    }
+
+    test_error_object(
+        test_eval("1?".to_string()),
+        EvalError::MismatchedObject {
+            expected: "RESULT".to_string(),
+            got: Object::Integer(1),
+        },
+    );
+}
+
+#[test]
+fn error_display() {
+    let tests = vec![
+        ("1 + true", "E0001", "type mismatch: INTEGER + BOOLEAN"),
+        ("-true", "E0002", "unknown operator: -BOOLEAN"),
+        (
+            "true & false",
+            "E0003",
+            "unknown operator: BOOLEAN & BOOLEAN",
+        ),
+        (
+            "\"a\" * \"b\"",
+            "E0004",
+            "unknown operator: STRING * STRING",
+        ),
+        ("foo", "E0005", "identifier not found: foo"),
+        ("let x = 1; x()", "E0006", "expected FUNCTION, got INTEGER"),
+        (
+            "fn(a, b) { a }(1)",
+            "E0007",
+            "wrong number of arguments: expected 2, got 1",
+        ),
+        ("1 / 0", "E0008", "division by zero"),
+        (
+            "1 >> -1",
+            "E0014",
+            "invalid shift amount: -1, expected 0 or more",
+        ),
+        ("throw \"oops\"", "E0017", "Error: oops"),
+    ];
+
+    for (input, code, message) in tests {
+        let err = test_eval(input.to_string()).unwrap_err();
+        assert_eq!(
+            (err.code(), err.to_string().as_str()),
+            (code, message),
+            "{}",
+            input
+        );
+    }
+
+    let tests = vec![
+        (
+            EvalError::StackOverflow { depth: 10 },
+            "E0009",
+            "stack overflow: more than 10 nested calls",
+        ),
+        (
+            EvalError::StepLimit { steps: 5 },
+            "E0010",
+            "step limit exceeded: more than 5 steps",
+        ),
+        (
+            EvalError::Timeout(Duration::from_millis(20)),
+            "E0011",
+            "timeout: ran for more than 20ms",
+        ),
+        (
+            EvalError::IntegerTooLarge { bits: 64 },
+            "E0018",
+            "integer too large: more than 64 bits",
+        ),
+        (
+            EvalError::ResultTooDeep { depth: 100 },
+            "E0019",
+            "result too deep: more than 100 nested results",
+        ),
+        (EvalError::Interrupted, "E0012", "interrupted"),
+        (
+            EvalError::OutOfMemory { limit: 64 },
+            "E0013",
+            "out of memory: more than 64 bytes allocated",
+        ),
+        (EvalError::Custom("oops".to_string()), "E0015", "oops"),
+        (EvalError::Unhandled, "E0016", "unhandled expression"),
+    ];
+    for (err, code, message) in tests {
+        assert_eq!((err.code(), err.to_string().as_str()), (code, message));
+    }
+}
+
+// Every code has an explanation, starting with a title
+#[test]
+fn error_explanations() {
+    for code in (1..=19).map(|i| format!("E{:04}", i)) {
+        match error::explain(&code) {
+            Some(explanation) => assert!(explanation.lines().nth(1) == Some(""), "{}", code),
+            None => panic!("{} has no explanation", code),
+        }
+    }
+    assert!(error::explain("e0008").is_some());
+    assert!(error::explain("E0020").is_none());
+}
@@ -0,0 +1,117 @@
+use super::EvalError;
+use crate::{object::Object, token::Span};
+use std::cell::RefCell;
+
+// A call in progress. A tail call replaces the frame of its caller
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    // The name the function was bound to with let, or the builtin called
+    pub function: Option<String>,
+    pub call_site: Span,
+}
+
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.function {
+            Some(function) => write!(f, "at {}, in {}", self.call_site, function),
+            None => write!(f, "at {}, in <anonymous>", self.call_site),
+        }
+    }
+}
+
+// An error returned by a program, with the calls in progress where it
+// happened
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuntimeError {
+    pub error: EvalError,
+    // Outermost first
+    pub trace: Vec<Frame>,
+}
+
+// Innermost call last, like Python tracebacks. Runs of the same frame, as
+// left by a recursion, are printed once
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.trace.is_empty() {
+            writeln!(f, "Traceback (most recent call last):")?;
+        }
+
+        let mut frames = self.trace.iter().peekable();
+        while let Some(frame) = frames.next() {
+            writeln!(f, "  {}", frame)?;
+
+            let mut repeated = 0;
+            while frames.next_if_eq(&frame).is_some() {
+                repeated += 1;
+            }
+            if repeated > 0 {
+                writeln!(f, "  [Previous frame repeated {} more times]", repeated)?;
+            }
+        }
+        write!(f, "error[{}]: {}", self.error.code(), self.error)
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+thread_local! {
+    static CALL_STACK: RefCell<Vec<Frame>> = const { RefCell::new(vec![]) };
+    // The calls in progress when the error being returned happened
+    static ERROR_TRACE: RefCell<Option<Vec<Frame>>> = const { RefCell::new(None) };
+}
+
+// Runs a call with its frame on the call stack
+pub(super) fn with_frame<T>(
+    frame: Frame,
+    call: impl FnOnce() -> Result<T, EvalError>,
+) -> Result<T, EvalError> {
+    CALL_STACK.with_borrow_mut(|stack| stack.push(frame));
+    let result = call();
+
+    CALL_STACK.with_borrow_mut(|stack| {
+        // The innermost call failing saves the stack, its callers only pass
+        // the error on
+        if result.is_err() {
+            ERROR_TRACE.with_borrow_mut(|trace| {
+                trace.get_or_insert_with(|| stack.clone());
+            });
+        }
+        stack.pop();
+    });
+    result
+}
+
+// Forgets the trace of an error that will not be returned
+pub(super) fn clear() {
+    ERROR_TRACE.take();
+}
+
+// The trace of the error being returned, if it happened in a call
+pub(super) fn take() -> Option<Vec<Frame>> {
+    ERROR_TRACE.take()
+}
+
+// Puts back a trace taken while running a finally clause
+pub(super) fn restore(trace: Option<Vec<Frame>>) {
+    ERROR_TRACE.set(trace);
+}
+
+// The calls in progress, outermost first
+pub(super) fn current() -> Vec<Frame> {
+    CALL_STACK.with_borrow(|stack| stack.clone())
+}
+
+pub(super) fn traced(error: EvalError) -> RuntimeError {
+    let trace = ERROR_TRACE.take().unwrap_or_default();
+
+    // A rethrown error keeps the stack of its first throw
+    let trace = match &error {
+        EvalError::Thrown(thrown) => match &**thrown {
+            Object::Error { stack, .. } => stack.clone(),
+            _ => trace,
+        },
+        _ => trace,
+    };
+
+    RuntimeError { error, trace }
+}
@@ -0,0 +1,142 @@
+// Layout documents in the style of Wadler's "prettier printer": a group is
+// printed on one line when it fits in the width, otherwise its lines break
+#[derive(Clone, Debug)]
+pub(super) enum Doc {
+    Text(String),
+    // A space, or a line break when the group is broken
+    Line,
+    // Nothing, or a line break when the group is broken
+    SoftLine,
+    // Always a line break, the enclosing group is broken too
+    HardLine,
+    // Printed just before the next line break, for trailing comments. The
+    // enclosing group is broken
+    LineSuffix(String),
+    // Indents the lines of its content when the enclosing group is broken
+    Indent(Box<Doc>),
+    Group(Box<Doc>),
+    // Always broken, its content is laid out on its own lines like a block
+    // while the enclosing group may stay on one line
+    Break(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+const INDENT: usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Flat,
+    Broken,
+}
+
+impl Doc {
+    pub(super) fn text(text: impl Into<String>) -> Self {
+        Doc::Text(text.into())
+    }
+
+    pub(super) fn indent(doc: Doc) -> Self {
+        Doc::Indent(Box::new(doc))
+    }
+
+    pub(super) fn group(doc: Doc) -> Self {
+        Doc::Group(Box::new(doc))
+    }
+
+    pub(super) fn broken(doc: Doc) -> Self {
+        Doc::Break(Box::new(doc))
+    }
+
+    // Whether the doc holds a forced line break outside of a Break
+    fn must_break(&self) -> bool {
+        match self {
+            Doc::HardLine | Doc::LineSuffix(_) => true,
+            Doc::Indent(doc) | Doc::Group(doc) => doc.must_break(),
+            Doc::Concat(docs) => docs.iter().any(Doc::must_break),
+            Doc::Text(_) | Doc::Line | Doc::SoftLine | Doc::Break(_) => false,
+        }
+    }
+
+    pub(super) fn layout(&self, width: usize) -> String {
+        let mut out = String::new();
+        let mut column = 0;
+        let mut suffixes = String::new();
+        let mut stack = vec![(0, Mode::Broken, self)];
+
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Doc::Text(text) => {
+                    out.push_str(text);
+                    column += text.chars().count();
+                }
+                Doc::Line if mode == Mode::Flat => {
+                    out.push(' ');
+                    column += 1;
+                }
+                Doc::SoftLine if mode == Mode::Flat => {}
+                Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                    out.push_str(&std::mem::take(&mut suffixes));
+                    out.truncate(out.trim_end_matches(' ').len());
+                    out.push('\n');
+                    out.push_str(&" ".repeat(indent));
+                    column = indent;
+                }
+                Doc::LineSuffix(text) => suffixes.push_str(text),
+                Doc::Indent(doc) => {
+                    let indent = match mode {
+                        Mode::Flat => indent,
+                        Mode::Broken => indent + INDENT,
+                    };
+                    stack.push((indent, mode, doc));
+                }
+                Doc::Group(doc) => {
+                    let mode = match mode {
+                        Mode::Flat => Mode::Flat,
+                        _ if doc.must_break() => Mode::Broken,
+                        _ if fits(doc, &stack, width.saturating_sub(column)) => Mode::Flat,
+                        _ => Mode::Broken,
+                    };
+                    stack.push((indent, mode, doc));
+                }
+                Doc::Break(doc) => stack.push((indent, Mode::Broken, doc)),
+                Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+            }
+        }
+
+        out.push_str(&suffixes);
+        out
+    }
+}
+
+// Whether the doc printed flat, followed by the rest of the stack, reaches
+// the next line break within the remaining width
+fn fits(doc: &Doc, rest: &[(usize, Mode, &Doc)], mut remaining: usize) -> bool {
+    let mut stack = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+
+    loop {
+        let (mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some((_, mode, doc)) => (*mode, *doc),
+                None => return true,
+            },
+        };
+
+        match doc {
+            Doc::Text(text) => match remaining.checked_sub(text.chars().count()) {
+                Some(left) => remaining = left,
+                None => return false,
+            },
+            Doc::Line if mode == Mode::Flat => match remaining.checked_sub(1) {
+                Some(left) => remaining = left,
+                None => return false,
+            },
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => return true,
+            Doc::LineSuffix(_) => {}
+            Doc::Indent(doc) | Doc::Group(doc) => stack.push((mode, doc)),
+            Doc::Break(doc) => stack.push((Mode::Broken, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (mode, doc))),
+        }
+    }
+}
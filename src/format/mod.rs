@@ -0,0 +1,342 @@
+#[cfg(test)]
+mod tests;
+
+mod doc;
+
+use crate::{
+    cst::{self, SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken, Trivia, TriviaKind},
+    parser::ParserError,
+    token::Token,
+};
+use doc::Doc;
+
+// Lines longer than this are broken where possible
+pub const MAX_WIDTH: usize = 100;
+
+// Formats the source in the canonical style: 4 spaces of indentation, one
+// statement per line ending with a semicolon, blocks on their own lines and
+// argument lists broken one per line when too long. Comments are kept, blank
+// lines are collapsed to one. Sources with errors are left untouched
+pub fn format(input: String) -> Result<String, Vec<ParserError>> {
+    format_with_width(input, MAX_WIDTH)
+}
+
+pub fn format_with_width(input: String, width: usize) -> Result<String, Vec<ParserError>> {
+    let (tree, errors) = cst::parse(input);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut formatter = Formatter {
+        leading_printed: false,
+    };
+    let Some(doc) = formatter.statements(&tree) else {
+        return Ok(String::new());
+    };
+
+    let mut out = doc.layout(width);
+    out.push('\n');
+    Ok(out)
+}
+
+struct Formatter {
+    // Set when the leading comments of the next token were already printed,
+    // as for the first token of a statement
+    leading_printed: bool,
+}
+
+impl Formatter {
+    // The statements of a program or a block, along with the comments after
+    // the last one. None if there is nothing at all
+    fn statements(&mut self, node: &SyntaxNode) -> Option<Doc> {
+        let mut docs = vec![];
+
+        for (i, statement) in node.nodes().enumerate() {
+            if i > 0 {
+                docs.push(Doc::HardLine);
+            }
+            docs.push(self.statement(statement, i == 0));
+        }
+
+        // The closing brace or Eof holds the comments after the last statement
+        if let Some(closing) = node.tokens().last() {
+            let mut comments = self.comments(&closing.leading, true, docs.is_empty());
+            if matches!(comments.last(), Some(Doc::HardLine | Doc::Text(_))) {
+                comments.pop();
+            }
+            if !comments.is_empty() && !docs.is_empty() {
+                docs.push(Doc::HardLine);
+            }
+            docs.extend(comments);
+        }
+
+        match docs.is_empty() {
+            true => None,
+            false => Some(Doc::Concat(docs)),
+        }
+    }
+
+    fn statement(&mut self, node: &SyntaxNode, first: bool) -> Doc {
+        // Blank lines before the statement are kept, except at the start
+        let mut docs = vec![];
+        if let Some(token) = first_token(node) {
+            docs.extend(self.comments(&token.leading, true, first));
+            self.leading_printed = true;
+        }
+
+        let semicolon = node.tokens().find(|token| token.token == Token::Semicolon);
+
+        for child in &node.children {
+            match child {
+                SyntaxElement::Node(expr) => docs.push(self.expression(expr)),
+                SyntaxElement::Token(token) if token.token == Token::Semicolon => {}
+                SyntaxElement::Token(token) => {
+                    docs.push(self.token(token));
+                    docs.push(Doc::text(" "));
+                }
+            }
+        }
+
+        let is_if = node.kind == SyntaxKind::ExpressionStatement
+            && matches!(
+                node.nodes().next().map(|expr| expr.kind),
+                Some(SyntaxKind::IfExpression | SyntaxKind::TryExpression)
+            );
+
+        match (semicolon, is_if) {
+            (Some(semicolon), false) => docs.push(self.token(semicolon)),
+            (None, false) => docs.push(Doc::text(";")),
+            // An if or try statement reads better without one, its comments stay
+            (Some(semicolon), true) => docs.push(self.trivia_only(semicolon)),
+            (None, true) => {}
+        }
+
+        Doc::Concat(docs)
+    }
+
+    fn expression(&mut self, node: &SyntaxNode) -> Doc {
+        let mut docs = vec![];
+        let mut previous = None;
+
+        for child in &node.children {
+            let doc = match child {
+                SyntaxElement::Node(child) if child.kind == SyntaxKind::Block => self.block(child),
+                SyntaxElement::Node(child)
+                    if matches!(
+                        child.kind,
+                        SyntaxKind::ParameterList | SyntaxKind::ArgumentList
+                    ) =>
+                {
+                    self.list(child)
+                }
+                SyntaxElement::Node(child) => self.expression(child),
+                SyntaxElement::Token(token) => self.token(token),
+            };
+
+            if let Some(prev) = previous.replace(child) {
+                if spaced(node.kind, prev, child) {
+                    docs.push(Doc::text(" "));
+                }
+            }
+            docs.push(doc);
+        }
+
+        Doc::Concat(docs)
+    }
+
+    fn block(&mut self, node: &SyntaxNode) -> Doc {
+        let mut tokens = node.tokens();
+        let (Some(open), Some(close)) = (tokens.next(), tokens.last()) else {
+            return Doc::Concat(vec![]);
+        };
+
+        let open = self.token(open);
+        match self.statements(node) {
+            None => Doc::Concat(vec![open, self.trivia_only(close), Doc::text("}")]),
+            Some(body) => Doc::broken(Doc::Concat(vec![
+                open,
+                Doc::indent(Doc::Concat(vec![Doc::HardLine, body])),
+                Doc::HardLine,
+                self.token_text(close),
+            ])),
+        }
+    }
+
+    // Parameters or arguments, broken one per line when they do not fit
+    fn list(&mut self, node: &SyntaxNode) -> Doc {
+        let mut open = None;
+        let mut close = None;
+        let mut items = vec![];
+
+        for child in &node.children {
+            match child {
+                SyntaxElement::Token(token) if token.token == Token::LParen => {
+                    open = Some(self.token(token))
+                }
+                SyntaxElement::Token(token) if token.token == Token::RParen => close = Some(token),
+                SyntaxElement::Token(token) if token.token == Token::Comma => {
+                    items.push(self.token(token));
+                    items.push(Doc::Line);
+                }
+                SyntaxElement::Token(token) => items.push(self.token(token)),
+                SyntaxElement::Node(child) => items.push(self.expression(child)),
+            }
+        }
+
+        let open = open.unwrap_or_else(|| Doc::text("("));
+        // The comment ending the line of the closing paren goes after the
+        // group, it does not break the list
+        let (close, trailing) = match close {
+            Some(close) => (
+                Doc::Concat(self.leading(close, !items.is_empty())),
+                Doc::Concat(self.trailing(&close.trailing)),
+            ),
+            None => (Doc::text(")"), Doc::Concat(vec![])),
+        };
+
+        if items.is_empty() {
+            return Doc::Concat(vec![open, close, trailing]);
+        }
+
+        Doc::Concat(vec![
+            Doc::group(Doc::Concat(vec![
+                open,
+                Doc::indent(Doc::Concat(
+                    [vec![Doc::SoftLine], items].into_iter().flatten().collect(),
+                )),
+                Doc::SoftLine,
+                close,
+            ])),
+            trailing,
+        ])
+    }
+
+    // A token with the comments around it
+    fn token(&mut self, token: &SyntaxToken) -> Doc {
+        let mut docs = self.leading(token, true);
+        docs.extend(self.trailing(&token.trailing));
+        Doc::Concat(docs)
+    }
+
+    // A token with the comments before it. Punctuation sticks to what
+    // precedes it, so do its comments: a /* b */; when something precedes it,
+    // f(/* c */) otherwise
+    fn leading(&mut self, token: &SyntaxToken, preceded: bool) -> Vec<Doc> {
+        let mut docs = match std::mem::take(&mut self.leading_printed) {
+            true => vec![],
+            false => self.comments(&token.leading, false, false),
+        };
+
+        if matches!(token.token, Token::Semicolon | Token::Comma | Token::RParen)
+            && matches!(docs.last(), Some(Doc::Text(_)))
+        {
+            docs.pop();
+            if preceded {
+                docs.insert(0, Doc::text(" "));
+            }
+        }
+        docs.push(Doc::text(&token.text));
+        docs
+    }
+
+    // Only the text, for tokens whose comments were already printed
+    fn token_text(&mut self, token: &SyntaxToken) -> Doc {
+        let mut docs = vec![Doc::text(&token.text)];
+        docs.extend(self.trailing(&token.trailing));
+        Doc::Concat(docs)
+    }
+
+    // The comments of a token that is not printed
+    fn trivia_only(&mut self, token: &SyntaxToken) -> Doc {
+        let mut docs = self.comments(&token.leading, false, false);
+        docs.extend(self.trailing(&token.trailing));
+        Doc::Concat(docs)
+    }
+
+    fn trailing(&mut self, trivia: &[Trivia]) -> Vec<Doc> {
+        trivia
+            .iter()
+            .filter(|piece| piece.kind == TriviaKind::LineComment)
+            .map(|piece| Doc::LineSuffix(format!(" {}", piece.text)))
+            .collect()
+    }
+
+    // Comments in leading trivia. At the start of a statement they go on
+    // their own lines and a blank line before them is kept, unless first.
+    // Elsewhere block comments stay inline and line comments move to the end
+    // of the line
+    fn comments(&mut self, trivia: &[Trivia], statement: bool, mut first: bool) -> Vec<Doc> {
+        let mut docs = vec![];
+        // The previous token ends its line with a newline, if any
+        let mut line_start = true;
+        let mut blank = false;
+
+        for (i, piece) in trivia.iter().enumerate() {
+            match piece.kind {
+                TriviaKind::Whitespace => continue,
+                TriviaKind::Newline => {
+                    blank |= line_start;
+                    line_start = true;
+                    continue;
+                }
+                TriviaKind::LineComment if !statement => {
+                    docs.push(Doc::LineSuffix(format!(" {}", piece.text)));
+                    continue;
+                }
+                TriviaKind::LineComment | TriviaKind::BlockComment => {}
+            }
+
+            if statement && blank && !first {
+                docs.push(Doc::HardLine);
+            }
+            blank = false;
+            first = false;
+            line_start = false;
+
+            docs.push(Doc::text(&piece.text));
+
+            let ends_line = piece.kind == TriviaKind::LineComment
+                || statement
+                    && trivia[i + 1..]
+                        .iter()
+                        .find(|next| next.kind != TriviaKind::Whitespace)
+                        .is_some_and(|next| next.kind == TriviaKind::Newline);
+            docs.push(match ends_line {
+                true => Doc::HardLine,
+                false => Doc::text(" "),
+            });
+        }
+
+        if statement && blank && !first {
+            docs.push(Doc::HardLine);
+        }
+
+        docs
+    }
+}
+
+fn first_token(node: &SyntaxNode) -> Option<&SyntaxToken> {
+    match node.children.first()? {
+        SyntaxElement::Token(token) => Some(token),
+        SyntaxElement::Node(node) => first_token(node),
+    }
+}
+
+// Whether a space goes between two children of an expression
+fn spaced(kind: SyntaxKind, prev: &SyntaxElement, next: &SyntaxElement) -> bool {
+    let is_token = |element: &SyntaxElement, token: Token| matches!(element, SyntaxElement::Token(t) if t.token == token);
+
+    match kind {
+        SyntaxKind::InfixExpression => true,
+        // if (x) { ... } else { ... }
+        // try { ... } catch (e) { ... } finally { ... }
+        SyntaxKind::IfExpression | SyntaxKind::TryExpression => {
+            !is_token(prev, Token::LParen) && !is_token(next, Token::RParen)
+        }
+        // fn(x) { ... }
+        SyntaxKind::FunctionExpression => {
+            matches!(next, SyntaxElement::Node(node) if node.kind == SyntaxKind::Block)
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,138 @@
+use super::*;
+use crate::{
+    ast::{without_locations, Program},
+    lexer::Lexer,
+    parser::Parser,
+};
+
+fn parse_ast(input: &str) -> Program {
+    let mut parser = Parser::new(Lexer::new(input.to_string()));
+    let program = parser.parse_program().unwrap();
+    assert!(parser.errors.is_empty(), "{}: {:?}", input, parser.errors);
+    program
+}
+
+#[test]
+fn format_statements() {
+    let tests = vec![
+        ("", ""),
+        ("  \n// nothing\n", "// nothing\n"),
+        ("let x=5", "let x = 5;\n"),
+        ("let x = 5 ;return x", "let x = 5;\nreturn x;\n"),
+        ("-a*(b+c)>>2|~d", "-a * (b + c) >> 2 | ~d;\n"),
+        ("add(1,2 ,3);f()", "add(1, 2, 3);\nf();\n"),
+        ("let s = \"a\\n\"+0x1F", "let s = \"a\\n\" + 0x1F;\n"),
+        (
+            "if(x<1){1}else{return 2;}",
+            "if (x < 1) {\n    1;\n} else {\n    return 2;\n}\n",
+        ),
+        (
+            "let add=fn(a,b){a+b};",
+            "let add = fn(a, b) {\n    a + b;\n};\n",
+        ),
+        ("let f = fn() {};", "let f = fn() {};\n"),
+        (
+            "map(arr, fn(x) { x * 2 })",
+            "map(arr, fn(x) {\n    x * 2;\n});\n",
+        ),
+        (
+            "try{f()}catch(e){throw e}finally{g()}",
+            "try {\n    f();\n} catch (e) {\n    throw e;\n} finally {\n    g();\n}\n",
+        ),
+        (
+            "let x = try { 1 } catch (e) { 0 };",
+            "let x = try {\n    1;\n} catch (e) {\n    0;\n};\n",
+        ),
+        ("let x = f(a) ? + (b)?", "let x = f(a)? + (b)?;\n"),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(format(input.to_string()).unwrap(), expected, "{}", input);
+    }
+}
+
+#[test]
+fn format_comments() {
+    let tests = vec![
+        (
+            "// header\n\n\n\nlet x = 5 // five\nlet y = /* six */ 6;",
+            "// header\n\nlet x = 5; // five\nlet y = /* six */ 6;\n",
+        ),
+        (
+            "let f = fn(x) { // body\n  x\n\n\n  /* end */\n}",
+            "let f = fn(x) { // body\n    x;\n\n    /* end */\n};\n",
+        ),
+        (
+            "if (x) { 1 }; // done\n// trailing",
+            "if (x) {\n    1;\n} // done\n// trailing\n",
+        ),
+        ("f(a, // first\n b)", "f(\n    a, // first\n    b\n);\n"),
+        ("x\n/* a */ /* b */\ny", "x;\n/* a */ /* b */\ny;\n"),
+        ("f(a) // end", "f(a); // end\n"),
+        ("let x = g(1) // c", "let x = g(1); // c\n"),
+        ("f(/* c */)", "f(/* c */);\n"),
+        ("f(a /* c */)", "f(a /* c */);\n"),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(format(input.to_string()).unwrap(), expected, "{}", input);
+    }
+}
+
+#[test]
+fn format_width() {
+    let input = "let result = compute(first_argument, second_argument, third_argument);";
+    let expected = "let result = compute(\n    first_argument,\n    second_argument,\n    third_argument\n);\n";
+    assert_eq!(format_with_width(input.to_string(), 40).unwrap(), expected);
+
+    // Only the outer list breaks when the inner one then fits
+    let input = "outer(inner(aaaaaaaa, bbbbbbbb), cccccccc, dddddddd)";
+    let expected = "outer(\n    inner(aaaaaaaa, bbbbbbbb),\n    cccccccc,\n    dddddddd\n);\n";
+    assert_eq!(format_with_width(input.to_string(), 40).unwrap(), expected);
+}
+
+#[test]
+fn format_errors() {
+    let tests = vec![
+        "let = 5;",
+        "fn(x) { let = ; x }",
+        "let x = @;",
+        "try { 1 }",
+        "try { 1 } catch { 2 }",
+    ];
+
+    for input in tests {
+        assert!(format(input.to_string()).is_err(), "{}", input);
+    }
+}
+
+#[test]
+fn round_trip() {
+    let tests = vec![
+        "let x = 5; // five\nx",
+        "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } };\nfib(10);",
+        "let big = 99999999999999999999; let f = 1.5e3; \"s\\t\" + \"t\"",
+        "if (!(a == b) != false) { return ~1 << 2 ^ 3 & 4; }",
+        "/* a */ let /* b */ x /* c */ = /* d */ 1 /* e */ ; /* f */",
+        "let apply = fn(f, x) { f(x) }; apply(fn(y) { y * y }, apply(fn(z) { z }, 3))",
+        "callee(aaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbbbbbbbbbb, fn(x, y) { x + y }, ccccccccccccccccccccc, dddd)",
+        "let safe = fn(x) { try { 10 / x } catch (err) { throw err } finally { return 0; } }; safe(0)",
+        "let quarter = fn(n) { let h = half(n)?; ok(half(h)?) }; -quarter(8)? * 2",
+        "f(a) // end",
+        "let x = g(1) // c",
+        "f(/* c */)",
+        "outer(inner(a) // c\n, b)",
+    ];
+
+    for input in tests {
+        let formatted = format(input.to_string()).unwrap();
+        assert_eq!(
+            without_locations(parse_ast(&formatted)),
+            without_locations(parse_ast(input)),
+            "{}",
+            formatted
+        );
+        // Formatting is idempotent
+        assert_eq!(format(formatted.clone()).unwrap(), formatted);
+    }
+}
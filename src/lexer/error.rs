@@ -0,0 +1,46 @@
+use crate::token::Position;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    // Where the offending token or char starts
+    pub position: Position,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LexErrorKind {
+    UnknownCharacter(char),
+    UnterminatedString,
+    UnterminatedComment,
+    // Holds the whole literal, e.g. 0xFG or 12abc
+    MalformedNumber(String),
+    // Holds the char following the backslash, '\0' at the end of the input
+    InvalidEscape(char),
+    InvalidUtf8(Vec<u8>),
+    // io::Error is not Clone, so only its message is kept
+    Io(String),
+}
+
+impl std::fmt::Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.position, self.kind)
+    }
+}
+
+impl std::fmt::Display for LexErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LexErrorKind::UnknownCharacter(ch) => write!(f, "unknown character {:?}", ch),
+            LexErrorKind::UnterminatedString => write!(f, "unterminated string"),
+            LexErrorKind::UnterminatedComment => write!(f, "unterminated block comment"),
+            LexErrorKind::MalformedNumber(literal) => {
+                write!(f, "malformed number literal {}", literal)
+            }
+            LexErrorKind::InvalidEscape(ch) => write!(f, "invalid escape sequence \\{}", ch),
+            LexErrorKind::InvalidUtf8(bytes) => write!(f, "invalid UTF-8 sequence {:02x?}", bytes),
+            LexErrorKind::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
@@ -1,51 +1,272 @@
-use crate::token::*;
+mod error;
+mod reader;
 
-#[derive(Debug)]
-pub(crate) struct Lexer {
-    input: String,
-    position: usize,
-    read_pos: usize,
+pub use error::{LexError, LexErrorKind};
+
+use crate::{
+    cst::{SyntaxToken, Trivia, TriviaKind},
+    token::*,
+};
+use num_bigint::BigInt;
+use reader::{CharReader, ReadError};
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Cursor, Read},
+};
+
+// The input is decoded lazily from a reader, which may borrow from the caller.
+// The lexer runs in linear time, and keeps the current token in memory rather
+// than the whole source. Comments skipped by next_token are not kept, the
+// syntax tokens keep them as trivia
+pub struct Lexer<'a> {
+    chars: CharReader<'a>,
+    // Chars decoded after ch, for peek_char, with their length in bytes
+    lookahead: VecDeque<(char, usize)>,
+    // Errors met while reading the input, with the byte offset they occurred at
+    read_errors: VecDeque<(usize, LexErrorKind)>,
+    // Every char consumed since the start of the current token, unless
+    // skipping
+    lexeme: String,
+    skipping: bool,
     ch: char,
+    ch_len: usize,
+    // Byte offset, line and column of ch
+    position: usize,
+    line: usize,
+    column: usize,
+}
+
+impl std::fmt::Debug for Lexer<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Lexer")
+            .field("ch", &self.ch)
+            .field("position", &self.position)
+            .field("line", &self.line)
+            .field("column", &self.column)
+            .finish_non_exhaustive()
+    }
 }
 
-impl Lexer {
-    pub(crate) fn new(input: String) -> Self {
+impl Iterator for Lexer<'_> {
+    type Item = Token;
+
+    // Stops at Token::Eof, which is not yielded
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_token() {
+            Token::Eof => None,
+            tok => Some(tok),
+        }
+    }
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: String) -> Self {
+        Self::from_buf_reader(Cursor::new(input.into_bytes()))
+    }
+
+    pub fn from_reader(reader: impl Read + 'a) -> Self {
+        Self::from_buf_reader(BufReader::new(reader))
+    }
+
+    pub fn from_buf_reader(reader: impl BufRead + 'a) -> Self {
         let mut lexer = Lexer {
-            input,
-            position: 0,
-            read_pos: 0,
+            chars: CharReader::new(reader),
+            lookahead: VecDeque::new(),
+            read_errors: VecDeque::new(),
+            lexeme: String::new(),
+            skipping: false,
             ch: '\0',
+            ch_len: 0,
+            position: 0,
+            line: 1,
+            column: 1,
         };
         lexer.read_char();
         lexer
     }
 
     fn read_char(&mut self) {
-        if self.read_pos >= self.input.len() {
-            self.ch = '\0';
-        } else {
-            // This is safe because we know that read_pos is less than input.len()
-            self.ch = self
-                .input
-                .chars()
-                .nth(self.read_pos)
-                .expect("Failed to get char");
+        if !self.at_end() {
+            if !self.skipping {
+                self.lexeme.push(self.ch);
+            }
+            self.position += self.ch_len;
+            if self.ch == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+
+        self.fill_lookahead(0);
+
+        (self.ch, self.ch_len) = self.lookahead.pop_front().unwrap_or(('\0', 0));
+    }
+
+    // Nothing is read before the first char and after the last one, where ch
+    // is '\0'. A NUL char of the input is a char like any other
+    fn at_end(&self) -> bool {
+        self.ch_len == 0
+    }
+
+    fn peek_char(&mut self) -> char {
+        self.peek_nth_char(0)
+    }
+
+    // Looks n chars past the next one, '\0' once the input is exhausted
+    fn peek_nth_char(&mut self, n: usize) -> char {
+        self.fill_lookahead(n);
+        self.lookahead.get(n).map_or('\0', |(ch, _)| *ch)
+    }
+
+    // Decodes chars until the lookahead holds at least n + 1 of them
+    fn fill_lookahead(&mut self, n: usize) {
+        while self.lookahead.len() <= n {
+            let offset = self.chars.offset();
+
+            match self.chars.next_char() {
+                Some(Ok(decoded)) => self.lookahead.push_back(decoded),
+                // Invalid bytes still take a place in the input so the error
+                // is reported where they are
+                Some(Err(err @ ReadError::InvalidUtf8(_))) => {
+                    let len = self.chars.offset() - offset;
+                    self.read_errors.push_back((offset, err.into()));
+                    self.lookahead.push_back((char::REPLACEMENT_CHARACTER, len));
+                }
+                Some(Err(err @ ReadError::Io(_))) => {
+                    self.read_errors.push_back((offset, err.into()));
+                    return;
+                }
+                None => return,
+            }
+        }
+    }
+
+    // Error met while reading the input at the current position, if any
+    fn take_read_error(&mut self) -> Option<LexErrorKind> {
+        // Errors inside comments are ignored
+        while let Some((offset, _)) = self.read_errors.front() {
+            if *offset >= self.position {
+                break;
+            }
+            self.read_errors.pop_front();
+        }
+
+        match self.read_errors.front() {
+            Some((offset, _)) if *offset == self.position => {
+                self.read_errors.pop_front().map(|(_, err)| err)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn next_token(&mut self) -> Token {
+        if let Err(err) = self.skip_whitespace_and_comments() {
+            return err;
+        }
+
+        self.read_token()
+    }
+
+    // Same as next_token, with the span of the token
+    pub(crate) fn next_token_with_span(&mut self) -> (Token, Span) {
+        let position = self.current_position();
+        if let Err(err) = self.skip_whitespace_and_comments() {
+            let end = self.current_position();
+            return (
+                err,
+                Span {
+                    start: position,
+                    end,
+                },
+            );
+        }
+
+        let start = self.current_position();
+        let token = self.read_token();
+        let end = self.current_position();
+        (token, Span { start, end })
+    }
+
+    // Same as next_token, but also keeps the text of the token and the
+    // whitespace and comments around it, for the lossless syntax tree
+    pub(crate) fn next_syntax_token(&mut self) -> SyntaxToken {
+        let mut leading = vec![];
+
+        loop {
+            self.lexeme.clear();
+            let position = self.current_position();
+
+            match self.read_trivia() {
+                Some(Ok(kind)) => leading.push(self.take_trivia(kind)),
+                // An unterminated comment is reported as a token of its own
+                Some(Err(token)) => {
+                    return SyntaxToken {
+                        token,
+                        text: std::mem::take(&mut self.lexeme),
+                        position,
+                        leading,
+                        trailing: vec![],
+                    }
+                }
+                None => break,
+            }
+        }
+
+        let position = self.current_position();
+        let token = self.read_token();
+        let text = std::mem::take(&mut self.lexeme);
+
+        // Whitespace and line comments up to the end of the line belong to the
+        // token, anything after goes to the next one
+        let mut trailing = vec![];
+        while token != Token::Eof && !self.at_end() {
+            self.lexeme.clear();
+
+            let kind = match (self.ch, self.peek_char()) {
+                ('/', '/') => TriviaKind::LineComment,
+                ('\n', _) => TriviaKind::Newline,
+                (ch, _) if ch.is_whitespace() => TriviaKind::Whitespace,
+                _ => break,
+            };
+            let _ = self.read_trivia();
+            trailing.push(self.take_trivia(kind));
+
+            if kind == TriviaKind::Newline {
+                break;
+            }
+        }
+
+        SyntaxToken {
+            token,
+            text,
+            position,
+            leading,
+            trailing,
         }
-        self.position = self.read_pos;
-        self.read_pos += 1;
     }
 
-    fn peek_char(&self) -> char {
-        if self.read_pos >= self.input.len() {
-            '0'
-        } else {
-            // This is safe because we know that read_pos is less than input.len()
-            self.input.chars().nth(self.read_pos).unwrap()
+    fn take_trivia(&mut self, kind: TriviaKind) -> Trivia {
+        Trivia {
+            kind,
+            text: std::mem::take(&mut self.lexeme),
         }
     }
 
-    pub(crate) fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+    // Reads the token starting at ch, once whitespace and comments are skipped
+    fn read_token(&mut self) -> Token {
+        self.lexeme.clear();
+
+        let start = self.current_position();
+
+        if let Some(kind) = self.take_read_error() {
+            self.read_char();
+            return Token::Illegal(LexError {
+                kind,
+                position: start,
+            });
+        }
 
         let tok = match self.ch {
             '=' => match self.peek_char() {
@@ -66,22 +287,40 @@ impl Lexer {
             },
             '*' => Token::Asterisk,
             '/' => Token::Slash,
-            '<' => Token::LessThan,
-            '>' => Token::GreaterThan,
+            '<' => match self.peek_char() {
+                '<' => {
+                    self.read_char();
+                    Token::ShiftLeft
+                }
+                _ => Token::LessThan,
+            },
+            '>' => match self.peek_char() {
+                '>' => {
+                    self.read_char();
+                    Token::ShiftRight
+                }
+                _ => Token::GreaterThan,
+            },
+            '&' => Token::Ampersand,
+            '|' => Token::Pipe,
+            '^' => Token::Caret,
+            '~' => Token::Tilde,
+            '?' => Token::Question,
             ';' => Token::Semicolon,
             '(' => Token::LParen,
             ')' => Token::RParen,
             ',' => Token::Comma,
             '{' => Token::LBrace,
             '}' => Token::RBrace,
-            '\0' => Token::Eof,
+            '"' => return self.read_string(),
+            '\0' if self.at_end() => Token::Eof,
             _ => {
-                if self.is_valid_identifier_char(self.ch) {
+                if self.is_identifier_start(self.ch) {
                     return self.read_identifier();
-                } else if self.ch.is_digit(10) {
+                } else if self.ch.is_ascii_digit() {
                     return self.read_number();
                 } else {
-                    Token::Illegal(self.ch.to_string())
+                    self.error(LexErrorKind::UnknownCharacter(self.ch), start)
                 }
             }
         };
@@ -91,13 +330,11 @@ impl Lexer {
     }
 
     fn read_identifier(&mut self) -> Token {
-        let pos = self.position;
-
-        while self.is_valid_identifier_char(self.ch) {
+        while self.is_identifier_continue(self.ch) {
             self.read_char();
         }
 
-        let identifier = self.input[pos..self.position].to_string();
+        let identifier = self.lexeme.clone();
 
         match identifier.as_str() {
             "fn" => Token::Function,
@@ -107,31 +344,269 @@ impl Lexer {
             "if" => Token::If,
             "else" => Token::Else,
             "return" => Token::Return,
+            "throw" => Token::Throw,
+            "try" => Token::Try,
+            "catch" => Token::Catch,
+            "finally" => Token::Finally,
             _ => Token::Ident(identifier),
         }
     }
 
     fn read_number(&mut self) -> Token {
-        let pos = self.position;
+        if self.ch == '0' {
+            match self.peek_char() {
+                'x' | 'X' => return self.read_radix_number(16),
+                'o' | 'O' => return self.read_radix_number(8),
+                'b' | 'B' => return self.read_radix_number(2),
+                _ => {}
+            }
+        }
+
+        self.read_digits();
+
+        let mut is_float = false;
+
+        // Fractional part, a digit is required after the dot
+        if self.ch == '.' && self.peek_char().is_ascii_digit() {
+            is_float = true;
+            self.read_char();
+            self.read_digits();
+        }
+
+        // Exponent part, e.g. 1e9, 2.5E-3
+        if matches!(self.ch, 'e' | 'E')
+            && (self.peek_char().is_ascii_digit()
+                || (matches!(self.peek_char(), '+' | '-')
+                    && self.peek_nth_char(1).is_ascii_digit()))
+        {
+            is_float = true;
+            self.read_char();
+            if matches!(self.ch, '+' | '-') {
+                self.read_char();
+            }
+            self.read_digits();
+        }
+
+        // A number directly followed by letters, e.g. 12abc, is malformed
+        if self.is_identifier_continue(self.ch) {
+            return self.read_malformed_number();
+        }
+
+        let literal = self.lexeme.replace('_', "");
+
+        if is_float {
+            // Literals too large for an f64 are rejected rather than becoming inf
+            return match literal.parse::<f64>() {
+                Ok(nb) if nb.is_finite() => Token::Float(nb),
+                _ => self.malformed_number(),
+            };
+        }
+
+        // Literals that overflow an i64 are kept as arbitrary-precision integers
+        match literal.parse::<i64>() {
+            Ok(nb) => Token::Int(nb),
+            Err(_) => match literal.parse::<BigInt>() {
+                Ok(nb) => Token::BigInt(nb),
+                Err(_) => self.malformed_number(),
+            },
+        }
+    }
+
+    // 0xFF, 0o755, 0b1010
+    fn read_radix_number(&mut self, radix: u32) -> Token {
+        // Skip the 0x, 0o or 0b prefix
+        self.read_char();
+        self.read_char();
+
+        // Read every identifier char so that 0b102, 0xFG or 0xFFé is a single
+        // bad token
+        while self.is_identifier_continue(self.ch) {
+            self.read_char();
+        }
+
+        // Underscores are only allowed between two digits
+        let separated = &self.lexeme[2..];
+        if separated
+            .split('_')
+            .any(|digits| digits.is_empty() || !digits.chars().all(|ch| ch.is_digit(radix)))
+        {
+            return self.malformed_number();
+        }
+        let digits = separated.replace('_', "");
+
+        match i64::from_str_radix(&digits, radix) {
+            Ok(nb) => Token::Int(nb),
+            Err(_) => match BigInt::parse_bytes(digits.as_bytes(), radix) {
+                Some(nb) => Token::BigInt(nb),
+                None => self.malformed_number(),
+            },
+        }
+    }
 
-        while self.ch.is_digit(10) {
+    fn read_malformed_number(&mut self) -> Token {
+        while self.is_identifier_continue(self.ch) {
             self.read_char();
         }
 
-        Token::Int(
-            self.input[pos..self.position]
-                .to_string()
-                .parse()
-                .expect("parse() failed"),
-        )
+        self.malformed_number()
+    }
+
+    // Decimal digits, an underscore is allowed as a separator between two
+    // digits e.g. 1_000_000. Any other underscore makes the number malformed
+    fn read_digits(&mut self) {
+        while self.ch.is_ascii_digit() || (self.ch == '_' && self.peek_char().is_ascii_digit()) {
+            self.read_char();
+        }
+    }
+
+    // Identifiers follow Unicode UAX #31 like Rust: XID_Start or `_` first,
+    // then XID_Continue, which includes digits
+    fn is_identifier_start(&self, ch: char) -> bool {
+        unicode_ident::is_xid_start(ch) || ch == '_'
     }
 
-    fn is_valid_identifier_char(&self, ch: char) -> bool {
-        ch.is_alphabetic() || ch == '_'
+    fn is_identifier_continue(&self, ch: char) -> bool {
+        unicode_ident::is_xid_continue(ch)
     }
 
-    fn skip_whitespace(&mut self) {
-        while self.ch.is_whitespace() {
+    // "...", with \n, \t, \r, \0, \\ and \" escapes
+    fn read_string(&mut self) -> Token {
+        let start = self.current_position();
+        let mut str = String::new();
+        let mut invalid_escape = None;
+
+        // Skip the opening quote
+        self.read_char();
+
+        loop {
+            match self.ch {
+                '"' => break,
+                '\0' if self.at_end() => {
+                    return self.error(LexErrorKind::UnterminatedString, start)
+                }
+                '\\' => {
+                    let escape_position = self.current_position();
+                    self.read_char();
+                    match self.ch {
+                        'n' => str.push('\n'),
+                        't' => str.push('\t'),
+                        'r' => str.push('\r'),
+                        '0' => str.push('\0'),
+                        '\\' => str.push('\\'),
+                        '"' => str.push('"'),
+                        // Keep going so that the whole string is consumed
+                        ch => {
+                            invalid_escape.get_or_insert((ch, escape_position));
+                            if self.at_end() {
+                                continue;
+                            }
+                        }
+                    }
+                }
+                ch => str.push(ch),
+            }
+            self.read_char();
+        }
+
+        // Skip the closing quote
+        self.read_char();
+
+        match invalid_escape {
+            Some((ch, position)) => self.error(LexErrorKind::InvalidEscape(ch), position),
+            None => Token::Str(str),
+        }
+    }
+
+    fn current_position(&self) -> Position {
+        Position {
+            offset: self.position,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn error(&self, kind: LexErrorKind, position: Position) -> Token {
+        Token::Illegal(LexError { kind, position })
+    }
+
+    // The number starts at the beginning of the lexeme
+    fn malformed_number(&self) -> Token {
+        let position = Position {
+            offset: self.position - self.lexeme.len(),
+            line: self.line,
+            column: self.column - self.lexeme.chars().count(),
+        };
+
+        self.error(LexErrorKind::MalformedNumber(self.lexeme.clone()), position)
+    }
+
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), Token> {
+        self.skipping = true;
+        let mut skipped = Ok(());
+        while let Some(trivia) = self.read_trivia() {
+            if let Err(err) = trivia {
+                skipped = Err(err);
+                break;
+            }
+        }
+        self.skipping = false;
+        skipped
+    }
+
+    // Reads one piece of whitespace or one comment, None if ch starts a token.
+    // Comments are either `// ...` up to the end of the line or `/* ... */`,
+    // block comments can be nested
+    fn read_trivia(&mut self) -> Option<Result<TriviaKind, Token>> {
+        let kind = match (self.ch, self.peek_char()) {
+            ('\n', _) => {
+                self.read_char();
+                TriviaKind::Newline
+            }
+            (ch, _) if ch.is_whitespace() => {
+                while self.ch.is_whitespace() && self.ch != '\n' {
+                    self.read_char();
+                }
+                TriviaKind::Whitespace
+            }
+            ('/', '/') => {
+                while self.ch != '\n' && !self.at_end() {
+                    self.read_char();
+                }
+                TriviaKind::LineComment
+            }
+            ('/', '*') => match self.skip_block_comment() {
+                Ok(()) => TriviaKind::BlockComment,
+                Err(err) => return Some(Err(err)),
+            },
+            _ => return None,
+        };
+
+        Some(Ok(kind))
+    }
+
+    fn skip_block_comment(&mut self) -> Result<(), Token> {
+        let start = self.current_position();
+        let mut depth = 0;
+
+        loop {
+            match (self.ch, self.peek_char()) {
+                ('/', '*') => {
+                    depth += 1;
+                    self.read_char();
+                }
+                ('*', '/') => {
+                    depth -= 1;
+                    self.read_char();
+                    if depth == 0 {
+                        self.read_char();
+                        return Ok(());
+                    }
+                }
+                ('\0', _) if self.at_end() => {
+                    return Err(self.error(LexErrorKind::UnterminatedComment, start))
+                }
+                _ => {}
+            }
             self.read_char();
         }
     }
@@ -141,6 +616,17 @@ impl Lexer {
 mod tests {
     use super::*;
 
+    fn illegal(kind: LexErrorKind, offset: usize, line: usize, column: usize) -> Token {
+        Token::Illegal(LexError {
+            kind,
+            position: Position {
+                offset,
+                line,
+                column,
+            },
+        })
+    }
+
     #[test]
     fn simple() -> Result<(), ()> {
         let input = "=+-(){}<>!*";
@@ -180,7 +666,7 @@ mod tests {
             };
 
             let result = add(five, ten);
-            !-/*5;
+            !-/ *5;
             5 < 10 > 5;
             if (5 < 10) {
                 return true;
@@ -277,4 +763,536 @@ mod tests {
             assert_eq!(&tok, t);
         }
     }
+
+    #[test]
+    fn big_integer_literal() {
+        let input = "9223372036854775807 9223372036854775808 123456789012345678901234567890";
+
+        let expected = vec![
+            Token::Int(i64::MAX),
+            Token::BigInt("9223372036854775808".parse().unwrap()),
+            Token::BigInt("123456789012345678901234567890".parse().unwrap()),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input.to_string());
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    #[test]
+    fn float_literal() {
+        let input = "3.25 1e-9 2.5E3 10e+2 0.5; 1.foo 7e 1e999";
+
+        let expected = vec![
+            Token::Float(3.25),
+            Token::Float(1e-9),
+            Token::Float(2.5e3),
+            Token::Float(10e2),
+            Token::Float(0.5),
+            Token::Semicolon,
+            Token::Int(1),
+            illegal(LexErrorKind::UnknownCharacter('.'), 28, 1, 29),
+            Token::Ident("foo".to_string()),
+            illegal(LexErrorKind::MalformedNumber("7e".to_string()), 33, 1, 34),
+            illegal(
+                LexErrorKind::MalformedNumber("1e999".to_string()),
+                36,
+                1,
+                37,
+            ),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input.to_string());
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    #[test]
+    fn radix_and_separated_literals() {
+        let input = "0xFF 0XdeadBEEF 0o755 0b1010 1_000_000 0b1111_0000 1_000.000_5 \
+            0xFFFFFFFFFFFFFFFFFF 007";
+
+        let expected = vec![
+            Token::Int(255),
+            Token::Int(0xdeadbeef),
+            Token::Int(0o755),
+            Token::Int(0b1010),
+            Token::Int(1_000_000),
+            Token::Int(0b1111_0000),
+            Token::Float(1_000.000_5),
+            Token::BigInt("4722366482869645213695".parse().unwrap()),
+            Token::Int(7),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input.to_string());
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    // Underscores are only allowed between two digits
+    #[test]
+    fn misplaced_separators() {
+        let input = "1_ 1__0 1_000_ 0x_FF 0xFF_ 0b1__0 1.5_ 1e_5 0xFFé 7;";
+
+        let expected = vec![
+            illegal(LexErrorKind::MalformedNumber("1_".to_string()), 0, 1, 1),
+            illegal(LexErrorKind::MalformedNumber("1__0".to_string()), 3, 1, 4),
+            illegal(LexErrorKind::MalformedNumber("1_000_".to_string()), 8, 1, 9),
+            illegal(
+                LexErrorKind::MalformedNumber("0x_FF".to_string()),
+                15,
+                1,
+                16,
+            ),
+            illegal(
+                LexErrorKind::MalformedNumber("0xFF_".to_string()),
+                21,
+                1,
+                22,
+            ),
+            illegal(
+                LexErrorKind::MalformedNumber("0b1__0".to_string()),
+                27,
+                1,
+                28,
+            ),
+            illegal(LexErrorKind::MalformedNumber("1.5_".to_string()), 34, 1, 35),
+            illegal(LexErrorKind::MalformedNumber("1e_5".to_string()), 39, 1, 40),
+            illegal(
+                LexErrorKind::MalformedNumber("0xFFé".to_string()),
+                44,
+                1,
+                45,
+            ),
+            Token::Int(7),
+            Token::Semicolon,
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input.to_string());
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    // A NUL char does not end the input
+    #[test]
+    fn nul_chars() {
+        let input = "let a = 1;\0 let b = \"a\0b\"; // \0 c\n/* \0 */ b";
+
+        let expected = vec![
+            Token::Let,
+            Token::Ident("a".to_string()),
+            Token::Assign,
+            Token::Int(1),
+            Token::Semicolon,
+            illegal(LexErrorKind::UnknownCharacter('\0'), 10, 1, 11),
+            Token::Let,
+            Token::Ident("b".to_string()),
+            Token::Assign,
+            Token::Str("a\0b".to_string()),
+            Token::Semicolon,
+            Token::Ident("b".to_string()),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input.to_string());
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    #[test]
+    fn malformed_number_literals() {
+        let input = "0x 0xFG 0o78 0b102 0b_ 12abc 3;";
+
+        let expected = vec![
+            illegal(LexErrorKind::MalformedNumber("0x".to_string()), 0, 1, 1),
+            illegal(LexErrorKind::MalformedNumber("0xFG".to_string()), 3, 1, 4),
+            illegal(LexErrorKind::MalformedNumber("0o78".to_string()), 8, 1, 9),
+            illegal(
+                LexErrorKind::MalformedNumber("0b102".to_string()),
+                13,
+                1,
+                14,
+            ),
+            illegal(LexErrorKind::MalformedNumber("0b_".to_string()), 19, 1, 20),
+            illegal(
+                LexErrorKind::MalformedNumber("12abc".to_string()),
+                23,
+                1,
+                24,
+            ),
+            Token::Int(3),
+            Token::Semicolon,
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input.to_string());
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        let input = "a & b | c ^ ~d << 2 >> 1 < > f()?";
+
+        let expected = vec![
+            Token::Ident("a".to_string()),
+            Token::Ampersand,
+            Token::Ident("b".to_string()),
+            Token::Pipe,
+            Token::Ident("c".to_string()),
+            Token::Caret,
+            Token::Tilde,
+            Token::Ident("d".to_string()),
+            Token::ShiftLeft,
+            Token::Int(2),
+            Token::ShiftRight,
+            Token::Int(1),
+            Token::LessThan,
+            Token::GreaterThan,
+            Token::Ident("f".to_string()),
+            Token::LParen,
+            Token::RParen,
+            Token::Question,
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input.to_string());
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    #[test]
+    fn exception_keywords() {
+        let input = "try catch finally throw trying thrown";
+
+        let expected = vec![
+            Token::Try,
+            Token::Catch,
+            Token::Finally,
+            Token::Throw,
+            Token::Ident("trying".to_string()),
+            Token::Ident("thrown".to_string()),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input.to_string());
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    #[test]
+    fn comments() {
+        let input = "// leading comment
+            let x = 5; // trailing comment
+            /* block */ x /* inline */ / 2;
+            /* outer /* nested */ still comment */ x
+            /**/ // last line without newline";
+
+        let expected = vec![
+            Token::Let,
+            Token::Ident("x".to_string()),
+            Token::Assign,
+            Token::Int(5),
+            Token::Semicolon,
+            Token::Ident("x".to_string()),
+            Token::Slash,
+            Token::Int(2),
+            Token::Semicolon,
+            Token::Ident("x".to_string()),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input.to_string());
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    #[test]
+    fn unterminated_block_comment() {
+        let input = "let x = 5;\n  x /* opened /* nested */ never closed";
+
+        let expected = vec![
+            Token::Let,
+            Token::Ident("x".to_string()),
+            Token::Assign,
+            Token::Int(5),
+            Token::Semicolon,
+            Token::Ident("x".to_string()),
+            illegal(LexErrorKind::UnterminatedComment, 15, 2, 5),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input.to_string());
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    #[test]
+    fn non_ascii_input() {
+        let input = "let café = \"€\"; /* ünïcödé 💖 */ naïve + 1; ∑";
+
+        let expected = vec![
+            Token::Let,
+            Token::Ident("café".to_string()),
+            Token::Assign,
+            Token::Str("€".to_string()),
+            Token::Semicolon,
+            Token::Ident("naïve".to_string()),
+            Token::Plus,
+            Token::Int(1),
+            Token::Semicolon,
+            illegal(LexErrorKind::UnknownCharacter('∑'), 54, 1, 44),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input.to_string());
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    #[test]
+    fn unterminated_block_comment_after_non_ascii() {
+        let input = "é\n ü /* never closed";
+
+        let mut lexer = Lexer::new(input.to_string());
+
+        assert_eq!(lexer.next_token(), Token::Ident("é".to_string()));
+        assert_eq!(lexer.next_token(), Token::Ident("ü".to_string()));
+        assert_eq!(
+            lexer.next_token(),
+            illegal(LexErrorKind::UnterminatedComment, 7, 2, 4)
+        );
+    }
+
+    #[test]
+    fn identifiers_with_digits_and_unicode() {
+        let input = "x1 utf8_decode _private __ résumé_日本語 Δx2 переменная1 x١ 2x 💖";
+
+        let expected = vec![
+            Token::Ident("x1".to_string()),
+            Token::Ident("utf8_decode".to_string()),
+            Token::Ident("_private".to_string()),
+            Token::Ident("__".to_string()),
+            Token::Ident("résumé_日本語".to_string()),
+            Token::Ident("Δx2".to_string()),
+            Token::Ident("переменная1".to_string()),
+            // Arabic-Indic digit one is XID_Continue
+            Token::Ident("x١".to_string()),
+            illegal(LexErrorKind::MalformedNumber("2x".to_string()), 77, 1, 58),
+            illegal(LexErrorKind::UnknownCharacter('💖'), 80, 1, 61),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input.to_string());
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    // Hands out the input one byte per read, splitting multi-byte chars
+    struct ByteByByte(Vec<u8>, usize);
+
+    impl Read for ByteByByte {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.get(self.1) {
+                Some(byte) if !buf.is_empty() => {
+                    buf[0] = *byte;
+                    self.1 += 1;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    // Fails with an I/O error once its input is exhausted
+    struct FailingReader(Cursor<Vec<u8>>);
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.read(buf)? {
+                0 => Err(std::io::Error::other("connection reset")),
+                n => Ok(n),
+            }
+        }
+    }
+
+    #[test]
+    fn streaming_from_reader() {
+        let input = "let naïve = fn(x) { x * 2.5 }; /* ☃ */ naïve(0xFF) // done";
+
+        let expected: Vec<Token> = Lexer::new(input.to_string()).collect();
+        let streamed: Vec<Token> =
+            Lexer::from_reader(ByteByByte(input.as_bytes().to_vec(), 0)).collect();
+
+        assert_eq!(streamed, expected);
+        assert_eq!(streamed.len(), 17);
+    }
+
+    // The reader can borrow, e.g. a slice or io::stdin().lock()
+    #[test]
+    fn borrowed_reader() {
+        let input = String::from("let x = 5;");
+
+        let tokens: Vec<Token> = Lexer::from_reader(input.as_bytes()).collect();
+        assert_eq!(tokens.len(), 5);
+
+        let tokens: Vec<Token> = Lexer::from_buf_reader(input.as_bytes()).collect();
+        assert_eq!(tokens.len(), 5);
+    }
+
+    // Skipped comments are not buffered, only the current token is
+    #[test]
+    fn comments_are_not_buffered() {
+        let comment = "x".repeat(1 << 20);
+        let input = format!("// {comment}\n/* {comment} */ 1 /* {comment}");
+
+        let mut lexer = Lexer::new(input);
+
+        assert_eq!(lexer.next_token(), Token::Int(1));
+        assert!(lexer.lexeme.capacity() < 64);
+        assert!(matches!(lexer.next_token(), Token::Illegal(_)));
+        assert!(lexer.lexeme.capacity() < 64);
+    }
+
+    #[test]
+    fn iterator_stops_at_eof() {
+        let mut lexer = Lexer::new("let x = 5;".to_string());
+
+        assert_eq!(
+            lexer.by_ref().collect::<Vec<_>>(),
+            vec![
+                Token::Let,
+                Token::Ident("x".to_string()),
+                Token::Assign,
+                Token::Int(5),
+                Token::Semicolon,
+            ]
+        );
+        assert_eq!(lexer.next(), None);
+        assert_eq!(lexer.next_token(), Token::Eof);
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let input = b"let \xFFx = a\xC3b; /* \xFE in a comment */ \xE2\x82";
+
+        let expected = vec![
+            Token::Let,
+            illegal(LexErrorKind::InvalidUtf8(vec![0xFF]), 4, 1, 5),
+            Token::Ident("x".to_string()),
+            Token::Assign,
+            Token::Ident("a".to_string()),
+            illegal(LexErrorKind::InvalidUtf8(vec![0xC3]), 10, 1, 11),
+            Token::Ident("b".to_string()),
+            Token::Semicolon,
+            illegal(LexErrorKind::InvalidUtf8(vec![0xE2, 0x82]), 35, 1, 36),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::from_reader(Cursor::new(input.to_vec()));
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    #[test]
+    fn io_error() {
+        let reader = FailingReader(Cursor::new(b"let x".to_vec()));
+
+        let expected = vec![
+            Token::Let,
+            Token::Ident("x".to_string()),
+            illegal(LexErrorKind::Io("connection reset".to_string()), 5, 1, 6),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::from_reader(reader);
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    #[test]
+    fn strings() {
+        let input = r#""hello" "" "a \"quoted\" \\ word\n\t\r\0" "multi
+line" "ünï""#;
+
+        let expected = vec![
+            Token::Str("hello".to_string()),
+            Token::Str("".to_string()),
+            Token::Str("a \"quoted\" \\ word\n\t\r\0".to_string()),
+            Token::Str("multi\nline".to_string()),
+            Token::Str("ünï".to_string()),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input.to_string());
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    #[test]
+    fn string_errors() {
+        let input = r#"let s = "bad \q escape \z"; "ok"
+  "never closed \"#;
+
+        let expected = vec![
+            Token::Let,
+            Token::Ident("s".to_string()),
+            Token::Assign,
+            illegal(LexErrorKind::InvalidEscape('q'), 13, 1, 14),
+            Token::Semicolon,
+            Token::Str("ok".to_string()),
+            illegal(LexErrorKind::UnterminatedString, 35, 2, 3),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input.to_string());
+
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    #[test]
+    fn error_display() {
+        let mut lexer = Lexer::new("\n  @".to_string());
+
+        let Token::Illegal(err) = lexer.next_token() else {
+            panic!("Expected an illegal token");
+        };
+        assert_eq!(err.to_string(), "2:3: unknown character '@'");
+    }
 }
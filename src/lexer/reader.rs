@@ -0,0 +1,100 @@
+use super::LexErrorKind;
+use std::io::{self, BufRead};
+
+// Decodes UTF-8 chars one at a time from a BufRead, so that the lexer never
+// needs the whole input in memory
+pub(super) struct CharReader<'a> {
+    reader: Box<dyn BufRead + 'a>,
+    // Number of bytes consumed so far
+    offset: usize,
+    // Set after an I/O error, nothing more is read
+    failed: bool,
+}
+
+pub(super) enum ReadError {
+    InvalidUtf8(Vec<u8>),
+    Io(io::Error),
+}
+
+impl From<ReadError> for LexErrorKind {
+    fn from(value: ReadError) -> Self {
+        match value {
+            ReadError::InvalidUtf8(bytes) => LexErrorKind::InvalidUtf8(bytes),
+            ReadError::Io(err) => LexErrorKind::Io(err.to_string()),
+        }
+    }
+}
+
+impl<'a> CharReader<'a> {
+    pub(super) fn new(reader: impl BufRead + 'a) -> Self {
+        CharReader {
+            reader: Box::new(reader),
+            offset: 0,
+            failed: false,
+        }
+    }
+
+    pub(super) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    // Returns the next char along with its length in bytes, None at the end of the input
+    pub(super) fn next_char(&mut self) -> Option<Result<(char, usize), ReadError>> {
+        let first = match self.next_byte(|_| true)? {
+            Ok(byte) => byte,
+            Err(err) => return Some(Err(err)),
+        };
+
+        let width = match first {
+            0x00..=0x7F => return Some(Ok((first as char, 1))),
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => return Some(Err(ReadError::InvalidUtf8(vec![first]))),
+        };
+
+        let mut bytes = vec![first];
+
+        // A byte that is not a continuation byte is left for the next char
+        while bytes.len() < width {
+            match self.next_byte(|byte| (0x80..=0xBF).contains(&byte)) {
+                Some(Ok(byte)) => bytes.push(byte),
+                Some(Err(err)) => return Some(Err(err)),
+                None => break,
+            }
+        }
+
+        // Also rejects overlong encodings and surrogates
+        match std::str::from_utf8(&bytes) {
+            Ok(str) => str.chars().next().map(|ch| Ok((ch, width))),
+            Err(_) => Some(Err(ReadError::InvalidUtf8(bytes))),
+        }
+    }
+
+    // Consumes the next byte only if accepted by the predicate
+    fn next_byte(&mut self, accept: impl Fn(u8) -> bool) -> Option<Result<u8, ReadError>> {
+        if self.failed {
+            return None;
+        }
+
+        loop {
+            match self.reader.fill_buf() {
+                Ok([]) => return None,
+                Ok([byte, ..]) => {
+                    let byte = *byte;
+                    if !accept(byte) {
+                        return None;
+                    }
+                    self.reader.consume(1);
+                    self.offset += 1;
+                    return Some(Ok(byte));
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(ReadError::Io(err)));
+                }
+            }
+        }
+    }
+}
@@ -1,20 +1,42 @@
 #[allow(dead_code)]
-mod ast;
+pub mod ast;
 
 #[allow(dead_code)]
 mod lexer;
+pub use lexer::Lexer;
 
 #[allow(dead_code)]
 mod parser;
+pub use parser::ParserError;
+
+pub mod cst;
+
+pub mod format;
+
+pub mod emit;
+
+pub mod optimizer;
 
 #[allow(dead_code)]
 mod object;
+pub use object::Object;
 
 #[allow(dead_code)]
 mod evaluation;
-pub use evaluation::environment::Environment;
+pub use evaluation::{
+    builtins::Builtin,
+    environment::Environment,
+    interpreter::{Interpreter, ProgramError},
+    limits::{Interrupt, Limits},
+    trace::{Frame, RuntimeError},
+    EvalError,
+};
 
 mod repl;
 pub use repl::Repl;
 
+mod cli;
+pub use cli::Cli;
+
 mod token;
+pub use token::{Position, Span, Token};
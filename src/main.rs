@@ -1,5 +1,12 @@
-use rust_interpreter::Repl;
+use rust_interpreter::Cli;
+use std::process::ExitCode;
 
-fn main() {
-    Repl::default().start();
+fn main() -> ExitCode {
+    match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli.run(),
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::from(2)
+        }
+    }
 }
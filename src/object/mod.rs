@@ -1,22 +1,50 @@
 use crate::{
     ast::{Expression, Statement},
+    evaluation::{builtins::Builtin, trace::Frame},
     Environment,
 };
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use std::rc::Rc;
 
 #[derive(Clone, Debug, Default, PartialEq)]
-pub(crate) enum Object {
+pub enum Object {
     #[default]
     Null,
     Integer(i64),
+    // Only used for values that do not fit in an i64, see From<BigInt>
+    BigInt(BigInt),
+    Float(f64),
+    // Copies of a string share it, so binding or passing it allocates nothing
+    Str(Rc<str>),
     Bool(bool),
     ReturnValue {
         value: Box<Object>,
     },
     Function {
+        // The name of the first let binding the function
+        name: Option<String>,
         // Should be Expression::Identifiers
         parameters: Vec<Expression>,
         body: Vec<Statement>,
-        env: Environment,
+        // The environment the function was created in, see Environment
+        env: Rc<Environment>,
+    },
+    Builtin(Builtin),
+    // Results, as made by ok() and err()
+    Ok {
+        value: Box<Object>,
+    },
+    Err {
+        value: Box<Object>,
+    },
+    // A thrown value or a runtime error, as bound by catch
+    Error {
+        // The EvalError variant, or the kind given to error()
+        kind: Rc<str>,
+        message: Rc<str>,
+        // The calls in progress where it was first thrown, outermost first
+        stack: Vec<Frame>,
     },
 }
 
@@ -25,6 +53,10 @@ impl std::fmt::Display for Object {
         match self {
             Object::Null => write!(f, "null"),
             Object::Integer(i) => write!(f, "{}", i),
+            Object::BigInt(i) => write!(f, "{}", i),
+            // Debug formatting always keeps the decimal point, e.g. 1.0
+            Object::Float(x) => write!(f, "{:?}", x),
+            Object::Str(str) => write!(f, "{}", str),
             Object::Bool(b) => write!(f, "{}", b),
             Object::ReturnValue { value } => write!(f, "{}", value),
             Object::Function {
@@ -49,18 +81,67 @@ impl std::fmt::Display for Object {
                         .join("\n ")
                 )
             }
+            Object::Builtin(builtin) => write!(f, "builtin {}", builtin),
+            Object::Ok { value } => write!(f, "ok({})", value),
+            Object::Err { value } => write!(f, "err({})", value),
+            Object::Error { kind, message, .. } => write!(f, "{}: {}", kind, message),
         }
     }
 }
 
 impl Object {
-    fn object_type(&self) -> String {
+    // Moves the environments this value keeps alive to envs, see Drop for
+    // Environment
+    pub(crate) fn take_environments(self, envs: &mut Vec<Rc<Environment>>) {
+        match self {
+            Object::Function { env, .. } => envs.push(env),
+            Object::ReturnValue { value } | Object::Ok { value } | Object::Err { value } => {
+                value.take_environments(envs)
+            }
+            _ => {}
+        }
+    }
+
+    // The environments this value keeps alive, see Environment::collect_cycles
+    pub(crate) fn for_each_environment(&self, f: &mut impl FnMut(&Rc<Environment>)) {
+        match self {
+            Object::Function { env, .. } => f(env),
+            Object::ReturnValue { value } | Object::Ok { value } | Object::Err { value } => {
+                value.for_each_environment(f)
+            }
+            Object::Null
+            | Object::Integer(_)
+            | Object::BigInt(_)
+            | Object::Float(_)
+            | Object::Str(_)
+            | Object::Bool(_)
+            | Object::Builtin(_)
+            | Object::Error { .. } => {}
+        }
+    }
+
+    pub fn object_type(&self) -> String {
         match self {
             Object::Null => "NULL".to_string(),
-            Object::Integer(_) => "INTEGER".to_string(),
+            Object::Integer(_) | Object::BigInt(_) => "INTEGER".to_string(),
+            Object::Float(_) => "FLOAT".to_string(),
+            Object::Str(_) => "STRING".to_string(),
             Object::Bool(_) => "BOOLEAN".to_string(),
             Object::ReturnValue { .. } => "RETURN_VALUE".to_string(),
             Object::Function { .. } => "FUNCTION".to_string(),
+            Object::Builtin(_) => "BUILTIN".to_string(),
+            Object::Ok { .. } | Object::Err { .. } => "RESULT".to_string(),
+            Object::Error { .. } => "ERROR".to_string(),
+        }
+    }
+}
+
+// Big integers are demoted back to Object::Integer whenever they fit
+impl From<BigInt> for Object {
+    fn from(value: BigInt) -> Self {
+        match value.to_i64() {
+            Some(i) => Object::Integer(i),
+            None => Object::BigInt(value),
         }
     }
 }
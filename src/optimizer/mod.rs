@@ -0,0 +1,149 @@
+// Rewrites the AST into an equivalent one that does less work at runtime:
+// - prefix and infix operations on literals are computed, with the same code
+//   as the evaluator but without counting against any limits. Operations that
+//   would fail, such as 1 / 0, are kept so that the error still happens when
+//   and if they run
+// - an if whose condition is a literal boolean is replaced by the branch taken
+// - statements after an unconditional return are dropped
+
+#[cfg(test)]
+mod tests;
+
+use crate::{
+    ast::{
+        visitor::{walk_block_mut, walk_expression_mut, walk_program_mut, walk_statement_mut},
+        Expression, Program, Statement, VisitorMut,
+    },
+    evaluation::{eval_expr_infix, eval_expr_prefix, limits::Meter},
+    object::Object,
+};
+
+pub fn optimize(program: &mut Program) {
+    Optimizer.visit_program_mut(program);
+}
+
+struct Optimizer;
+
+impl VisitorMut for Optimizer {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program);
+        drop_unreachable(&mut program.statements);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Vec<Statement>) {
+        walk_block_mut(self, block);
+        drop_unreachable(block);
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement);
+
+        // As a statement, the if is only there for the branch taken, which
+        // runs in the same environment as a block
+        if let Statement::Expression(expression) = statement {
+            if let Some(branch) = taken_branch(expression) {
+                *statement = Statement::Block(branch);
+            }
+        }
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+
+        match expression {
+            Expression::Prefix { operator, right } => {
+                if let Some(right) = literal(right) {
+                    if let Ok(folded) = eval_expr_prefix(operator.clone(), right, Meter::Unmetered)
+                    {
+                        replace_with_literal(expression, folded);
+                    }
+                }
+            }
+            Expression::Infix {
+                left,
+                operator,
+                right,
+            } => {
+                if let (Some(left), Some(right)) = (literal(left), literal(right)) {
+                    if let Ok(folded) =
+                        eval_expr_infix(operator.clone(), left, right, Meter::Unmetered)
+                    {
+                        replace_with_literal(expression, folded);
+                    }
+                }
+            }
+            Expression::If { .. } => {
+                let Some(mut branch) = taken_branch(expression) else {
+                    return;
+                };
+                // A branch of a single expression evaluates to that expression,
+                // others are kept in an if that always takes them
+                *expression = match branch.as_mut_slice() {
+                    [Statement::Expression(value)] => std::mem::take(value),
+                    _ => Expression::If {
+                        condition: Box::new(Expression::Bool(true)),
+                        consequence: branch,
+                        alternative: None,
+                    },
+                };
+            }
+            _ => {}
+        }
+    }
+}
+
+// The statements an if with a literal condition always runs
+fn taken_branch(expression: &mut Expression) -> Option<Vec<Statement>> {
+    let Expression::If {
+        condition,
+        consequence,
+        alternative,
+    } = expression
+    else {
+        return None;
+    };
+
+    match **condition {
+        Expression::Bool(true) => Some(std::mem::take(consequence)),
+        Expression::Bool(false) => Some(alternative.take().unwrap_or_default()),
+        _ => None,
+    }
+}
+
+fn drop_unreachable(block: &mut Vec<Statement>) {
+    if let Some(i) = block.iter().position(always_returns) {
+        block.truncate(i + 1);
+    }
+}
+
+// Return or throw, either way the statements after it never run
+fn always_returns(statement: &Statement) -> bool {
+    match statement {
+        Statement::Return(_) | Statement::Throw(_) => true,
+        Statement::Block(statements) => statements.iter().any(always_returns),
+        Statement::Let { .. } | Statement::Expression(_) => false,
+    }
+}
+
+fn literal(expression: &Expression) -> Option<Object> {
+    match expression {
+        Expression::Int(i) => Some(Object::Integer(*i)),
+        Expression::BigInt(i) => Some(Object::from(i.clone())),
+        Expression::Float(x) => Some(Object::Float(*x)),
+        Expression::Str(str) => Some(Object::Str(str.as_str().into())),
+        Expression::Bool(b) => Some(Object::Bool(*b)),
+        _ => None,
+    }
+}
+
+// Objects without a literal form leave the expression as it is
+fn replace_with_literal(expression: &mut Expression, obj: Object) {
+    *expression = match obj {
+        Object::Integer(i) => Expression::Int(i),
+        Object::BigInt(i) => Expression::BigInt(i),
+        Object::Float(x) => Expression::Float(x),
+        Object::Str(str) => Expression::Str(str.to_string()),
+        Object::Bool(b) => Expression::Bool(b),
+        _ => return,
+    };
+}
@@ -0,0 +1,161 @@
+use super::*;
+use crate::{
+    emit::to_sexpr, evaluation::environment::Environment, lexer::Lexer, parser::Parser, Limits,
+};
+
+fn parse(input: &str) -> Program {
+    let mut parser = Parser::new(Lexer::new(input.to_string()));
+    let program = parser.parse_program().unwrap();
+    assert!(parser.errors.is_empty(), "{}: {:?}", input, parser.errors);
+    program
+}
+
+// Before and after, as S-expressions without spans
+fn test_optimize(tests: Vec<(&str, &str)>) {
+    for (input, expected) in tests {
+        let mut program = parse(input);
+        optimize(&mut program);
+        assert_eq!(to_sexpr(&program, None), expected, "{}", input);
+    }
+}
+
+#[test]
+fn constant_folding() {
+    test_optimize(vec![
+        ("2 * 3 + 4", "(program\n  (expr (int 10)))\n"),
+        ("-(1 << 3) | ~0 ^ 5", "(program\n  (expr (int -6)))\n"),
+        ("x + 2 * 3", "(program\n  (expr (infix + (ident x) (int 6))))\n"),
+        ("1.5 * 2 == 3", "(program\n  (expr (bool true)))\n"),
+        (
+            "\"a\" + \"b\"",
+            "(program\n  (expr (infix + (string \"a\") (string \"b\"))))\n",
+        ),
+        (
+            "9223372036854775807 + 1",
+            "(program\n  (expr (int 9223372036854775808)))\n",
+        ),
+        (
+            "let f = fn(x) { x * (60 * 60) }",
+            "(program\n  (let f (fn (params (ident x)) (body (expr (infix * (ident x) (int 3600)))))))\n",
+        ),
+        ("f(1 + 1)", "(program\n  (expr (call (ident f) (args (int 2)))))\n"),
+    ]);
+}
+
+#[test]
+fn errors_are_kept() {
+    test_optimize(vec![
+        ("1 / 0", "(program\n  (expr (infix / (int 1) (int 0))))\n"),
+        // Only the failing operation stays
+        (
+            "(2 + 2) / (1 - 1)",
+            "(program\n  (expr (infix / (int 4) (int 0))))\n",
+        ),
+        (
+            "1 << -1",
+            "(program\n  (expr (infix << (int 1) (int -1))))\n",
+        ),
+        (
+            "true + 1",
+            "(program\n  (expr (infix + (bool true) (int 1))))\n",
+        ),
+        ("-\"a\"", "(program\n  (expr (prefix - (string \"a\"))))\n"),
+    ]);
+}
+
+#[test]
+fn dead_branches() {
+    test_optimize(vec![
+        (
+            "if (true) { a } else { b }",
+            "(program\n  (expr (ident a)))\n",
+        ),
+        ("if (1 > 2) { a }", "(program\n  (block))\n"),
+        (
+            "let x = if (1 < 2) { a } else { b };",
+            "(program\n  (let x (ident a)))\n",
+        ),
+        (
+            "let x = if (false) { a } else { let y = 1; y };",
+            "(program\n  (let x (if (bool true) (then (let y (int 1)) (expr (ident y))))))\n",
+        ),
+        (
+            "if (x) { a } else { b }",
+            "(program\n  (expr (if (ident x) (then (expr (ident a))) (else (expr (ident b))))))\n",
+        ),
+    ]);
+}
+
+#[test]
+fn unreachable_statements() {
+    test_optimize(vec![
+        (
+            "let a = 1; return a; a + 1; let b = 2;",
+            "(program\n  (let a (int 1))\n  (return (ident a)))\n",
+        ),
+        (
+            "fn() { if (x) { return 1; } 2; return 3; 4 }",
+            "(program\n  (expr (fn (params) (body (expr (if (ident x) (then (return (int 1))))) (expr (int 2)) (return (int 3))))))\n",
+        ),
+        (
+            "fn() { if (true) { return 1; } 2 }",
+            "(program\n  (expr (fn (params) (body (block (return (int 1)))))))\n",
+        ),
+        (
+            "try { throw 1; f() } catch (e) { 2 }",
+            "(program\n  (expr (try (body (throw (int 1))) (catch e (expr (int 2))))))\n",
+        ),
+    ]);
+}
+
+// The optimized program gives the same result or the same error
+#[test]
+fn same_semantics() {
+    let tests = vec![
+        "2 * 3 + 4",
+        "1 / 0",
+        "let x = 10; if (x > 5) { x * 2 } else { 0 }",
+        "if (false) { 1 }",
+        "if (true) { return 7; 8 }; 9",
+        "let f = fn(n) { if (1 == 1) { return n + 1 * 2; } n }; f(1)",
+        "let f = fn() { if (true) { let z = 3 } z }; f()",
+        "pow(2, 10) + 3 * 3",
+        "1 << 63",
+        "\"a\" + 1",
+        "let f = fn() { try { 1 / 0 } catch (e) { return 2 + 3; } finally { 4 } }; f()",
+        "let f = fn(r) { let x = r? * (2 + 3); ok(x) }; f(err(1 + 1))",
+    ];
+
+    for input in tests {
+        let expected = parse(input).eval(Environment::new_rc(), &Limits::default());
+
+        let mut program = parse(input);
+        optimize(&mut program);
+        let got = program.eval(Environment::new_rc(), &Limits::default());
+
+        assert_eq!(got, expected, "{}", input);
+    }
+}
+
+// Folding does not count against the limits of the programs run before
+#[test]
+fn folding_ignores_limits() {
+    let limits = Limits {
+        max_steps: Some(10),
+        max_allocated: Some(1),
+        ..Default::default()
+    };
+    let used_up = parse("let f = fn() { f() }; f()").eval(Environment::new_rc(), &limits);
+    assert!(used_up.is_err());
+
+    test_optimize(vec![
+        (
+            "9223372036854775807 + 1",
+            "(program\n  (expr (int 9223372036854775808)))\n",
+        ),
+        (
+            "-(1 << 100)",
+            "(program\n  (expr (int -1267650600228229401496703205376)))\n",
+        ),
+    ]);
+}
@@ -1,26 +1,39 @@
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
 mod tests;
 
 use crate::{
-    ast::{Expression, InfixOperator, PrefixOperator, Program, Statement},
-    lexer::Lexer,
-    token::Token,
+    ast::{Expression, InfixOperator, Location, PrefixOperator, Program, Statement},
+    cst::{SyntaxKind, SyntaxNode, SyntaxToken},
+    lexer::{LexError, Lexer},
+    token::{Position, Span, Token},
 };
+use num_bigint::BigInt;
 
 #[derive(Debug)]
-pub(crate) struct Parser {
-    lexer: Lexer,
+pub(crate) struct Parser<'a> {
+    lexer: Lexer<'a>,
     curr_token: Token,
     peek_token: Token,
-    pub(super) errors: Vec<ParserError>,
+    // Index of each token in the source, counting from 0
+    curr_index: usize,
+    peek_index: usize,
+    curr_span: Span,
+    peek_span: Span,
+    // Set in lossless mode, every token read with its trivia
+    syntax_tokens: Option<Vec<SyntaxToken>>,
+    // Token ranges of the nodes parsed so far, in lossless mode
+    syntax_nodes: Vec<(SyntaxKind, usize, usize)>,
+    pub(crate) errors: Vec<ParserError>,
 }
 
 #[derive(Clone, Debug)]
-pub(super) enum ParserError {
+pub enum ParserError {
     UnexpectedToken { expected: Token, got: Token },
     InvalidPrefixOperator { operator: Token },
     InvalidInfixOperator { operator: Token },
     MissRightParenthesis { operator: Token },
+    Lexer(LexError),
     UnhandledError,
 }
 
@@ -39,36 +52,104 @@ impl std::fmt::Display for ParserError {
             ParserError::MissRightParenthesis { operator } => {
                 write!(f, "Missing Closing parenthesis, got {} instead", operator)
             }
+            ParserError::Lexer(err) => write!(f, "{}", err),
             ParserError::UnhandledError => write!(f, "Unhandled error"),
         }
     }
 }
 
-impl Parser {
-    pub(crate) fn new(mut lexer: Lexer) -> Self {
-        let curr_token = lexer.next_token();
-        let peek_token = lexer.next_token();
+impl<'a> Parser<'a> {
+    pub(crate) fn new(lexer: Lexer<'a>) -> Self {
+        Self::with_syntax_tokens(lexer, None)
+    }
+
+    // Also records the tokens with their trivia and the extent of each node,
+    // for syntax_tree
+    pub(crate) fn new_lossless(lexer: Lexer<'a>) -> Self {
+        Self::with_syntax_tokens(lexer, Some(vec![]))
+    }
 
-        Parser {
+    fn with_syntax_tokens(lexer: Lexer<'a>, syntax_tokens: Option<Vec<SyntaxToken>>) -> Self {
+        let mut parser = Parser {
             lexer,
-            curr_token,
-            peek_token,
+            curr_token: Token::Eof,
+            peek_token: Token::Eof,
+            curr_index: 0,
+            peek_index: 0,
+            curr_span: Span::default(),
+            peek_span: Span::default(),
+            syntax_tokens,
+            syntax_nodes: vec![],
             errors: vec![],
-        }
+        };
+        parser.next_token();
+        parser.next_token();
+        parser
     }
 
     fn next_token(&mut self) {
-        self.curr_token = self.peek_token.clone();
-        self.peek_token = self.lexer.next_token();
+        self.curr_token = std::mem::take(&mut self.peek_token);
+        self.curr_index = self.peek_index;
+        self.curr_span = self.peek_span;
+
+        let Some(tokens) = &mut self.syntax_tokens else {
+            (self.peek_token, self.peek_span) = self.lexer.next_token_with_span();
+            self.peek_index += 1;
+            return;
+        };
+
+        // Only the first Eof is kept, the lexer keeps returning it
+        match tokens.last() {
+            Some(last) if last.token == Token::Eof => self.peek_token = Token::Eof,
+            _ => {
+                let token = self.lexer.next_syntax_token();
+                self.peek_token = token.token.clone();
+                self.peek_span = token.span();
+                tokens.push(token);
+            }
+        }
+        self.peek_index = tokens.len() - 1;
+    }
+
+    // The lossless syntax tree of what was parsed, after parse_program.
+    // Empty if the parser was not created with new_lossless
+    pub(crate) fn syntax_tree(&mut self) -> SyntaxNode {
+        let tokens = self.syntax_tokens.take().unwrap_or_default();
+        SyntaxNode::build(tokens, std::mem::take(&mut self.syntax_nodes))
+    }
+
+    // Records a node made of the tokens from start up to the current one
+    fn finish_node(&mut self, kind: SyntaxKind, start: usize) {
+        if self.syntax_tokens.is_some() {
+            self.syntax_nodes.push((kind, start, self.curr_index));
+        }
+    }
+
+    // Wraps the tokens of a statement that failed to parse, Eof excepted
+    fn finish_error_node(&mut self, start: usize) {
+        let end = match self.curr_token {
+            Token::Eof => self.curr_index.checked_sub(1),
+            _ => Some(self.curr_index),
+        };
+
+        if let Some(end) = end.filter(|end| *end >= start) {
+            if self.syntax_tokens.is_some() {
+                self.syntax_nodes.push((SyntaxKind::Error, start, end));
+            }
+        }
     }
 
     pub(crate) fn parse_program(&mut self) -> Result<Program, ()> {
         let mut program = Program { statements: vec![] };
 
         while self.curr_token != Token::Eof {
+            let start = self.curr_index;
             match self.parse_statement() {
                 Ok(stmt) => program.statements.push(stmt),
-                Err(e) => self.errors.push(e),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.finish_error_node(start);
+                }
             }
             self.next_token();
         }
@@ -80,11 +161,14 @@ impl Parser {
         match self.curr_token {
             Token::Let => self.parse_statement_let(),
             Token::Return => self.parse_statement_ret(),
+            Token::Throw => self.parse_statement_throw(),
             _ => self.parse_statement_expr(),
         }
     }
 
     fn parse_statement_let(&mut self) -> Result<Statement, ParserError> {
+        let start = self.curr_index;
+
         // First thing after the let keyword should be an identifier
         let Token::Ident(name) = self.peek_token.clone() else {
             return Err(ParserError::UnexpectedToken {
@@ -106,10 +190,13 @@ impl Parser {
             self.next_token();
         }
 
+        self.finish_node(SyntaxKind::LetStatement, start);
         Ok(Statement::Let { name, value })
     }
 
     fn parse_statement_ret(&mut self) -> Result<Statement, ParserError> {
+        let start = self.curr_index;
+
         // Skip the return keyword
         self.next_token();
 
@@ -120,45 +207,78 @@ impl Parser {
             self.next_token();
         }
 
+        self.finish_node(SyntaxKind::ReturnStatement, start);
         Ok(Statement::Return(expr))
     }
 
+    fn parse_statement_throw(&mut self) -> Result<Statement, ParserError> {
+        let start = self.curr_index;
+
+        // Skip the throw keyword
+        self.next_token();
+
+        let expr = self.parse_expression(Precedence::Lowest)?;
+
+        if self.peek_token_is(&Token::Semicolon) {
+            self.next_token();
+        }
+
+        self.finish_node(SyntaxKind::ThrowStatement, start);
+        Ok(Statement::Throw(expr))
+    }
+
     fn parse_statement_expr(&mut self) -> Result<Statement, ParserError> {
+        let start = self.curr_index;
         let expr = self.parse_expression(Precedence::Lowest)?;
 
         if self.peek_token_is(&Token::Semicolon) {
             self.next_token();
         }
 
+        self.finish_node(SyntaxKind::ExpressionStatement, start);
         Ok(Statement::Expression(expr))
     }
 
     fn parse_statement_block(&mut self) -> Vec<Statement> {
         let mut block: Vec<Statement> = vec![];
+        let start = self.curr_index;
 
         self.next_token();
 
         while !self.curr_token_is(&Token::RBrace) && !self.curr_token_is(&Token::Eof) {
-            let statement = self.parse_statement();
-            if let Ok(statement) = statement {
-                block.push(statement);
+            let statement_start = self.curr_index;
+            match self.parse_statement() {
+                Ok(statement) => block.push(statement),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.finish_error_node(statement_start);
+                }
             }
             self.next_token();
         }
 
+        self.finish_node(SyntaxKind::Block, start);
         block
     }
 
     fn parse_expression(&mut self, precedence: Precedence) -> Result<Expression, ParserError> {
+        let start = self.curr_index;
+        let start_position = self.curr_span.start;
+
         // This is in replacement of the prefix fns map in the book
         let mut left = match self.curr_token.clone() {
             Token::Ident(str) => self.parse_expr_identifier(&str),
             Token::Int(nb) => self.parse_expr_integer(nb),
-            Token::Bang | Token::Minus => self.parse_expr_prefix()?,
+            Token::BigInt(nb) => self.parse_expr_big_integer(nb),
+            Token::Float(nb) => self.parse_expr_float(nb),
+            Token::Str(str) => self.parse_expr_string(str),
+            Token::Bang | Token::Minus | Token::Tilde => self.parse_expr_prefix()?,
             Token::True | Token::False => self.parse_expr_boolean(),
             Token::LParen => self.parse_expr_grouped()?,
             Token::If => self.parse_expr_if()?,
             Token::Function => self.parse_expr_function()?,
+            Token::Try => self.parse_expr_try()?,
+            Token::Illegal(err) => return Err(ParserError::Lexer(err)),
             _ => {
                 return Err(ParserError::InvalidPrefixOperator {
                     operator: self.curr_token.clone(),
@@ -177,24 +297,55 @@ impl Parser {
                 | Token::Eq
                 | Token::NotEq
                 | Token::LessThan
-                | Token::GreaterThan => self.parse_expr_infix(&left)?,
-                Token::LParen => self.parse_expr_call(&left)?,
+                | Token::GreaterThan
+                | Token::Ampersand
+                | Token::Pipe
+                | Token::Caret
+                | Token::ShiftLeft
+                | Token::ShiftRight => self.parse_expr_infix(&left)?,
+                Token::LParen => self.parse_expr_call(&left, start_position)?,
+                Token::Question => Expression::Propagate(Box::new(left)),
                 _ => return Ok(left),
             };
+
+            let kind = match left {
+                Expression::FunctionCall { .. } => SyntaxKind::CallExpression,
+                Expression::Propagate(_) => SyntaxKind::PropagateExpression,
+                _ => SyntaxKind::InfixExpression,
+            };
+            self.finish_node(kind, start);
         }
 
         Ok(left)
     }
 
     fn parse_expr_identifier(&mut self, str: &str) -> Expression {
+        self.finish_node(SyntaxKind::Identifier, self.curr_index);
         Expression::Identifier(str.to_string())
     }
 
     fn parse_expr_integer(&mut self, nb: i64) -> Expression {
+        self.finish_node(SyntaxKind::Literal, self.curr_index);
         Expression::Int(nb)
     }
 
+    fn parse_expr_big_integer(&mut self, nb: BigInt) -> Expression {
+        self.finish_node(SyntaxKind::Literal, self.curr_index);
+        Expression::BigInt(nb)
+    }
+
+    fn parse_expr_float(&mut self, nb: f64) -> Expression {
+        self.finish_node(SyntaxKind::Literal, self.curr_index);
+        Expression::Float(nb)
+    }
+
+    fn parse_expr_string(&mut self, str: String) -> Expression {
+        self.finish_node(SyntaxKind::Literal, self.curr_index);
+        Expression::Str(str)
+    }
+
     fn parse_expr_prefix(&mut self) -> Result<Expression, ParserError> {
+        let start = self.curr_index;
         let Ok(prefix) = PrefixOperator::try_from(&self.curr_token) else {
             return Err(ParserError::InvalidPrefixOperator {
                 operator: self.curr_token.clone(),
@@ -203,8 +354,9 @@ impl Parser {
 
         self.next_token();
 
-        let expr = self.parse_expression(Precedence::Prefix).unwrap();
+        let expr = self.parse_expression(Precedence::Prefix)?;
 
+        self.finish_node(SyntaxKind::PrefixExpression, start);
         Ok(Expression::Prefix {
             operator: prefix,
             right: Box::new(expr),
@@ -225,20 +377,31 @@ impl Parser {
         })
     }
 
-    fn parse_expr_call(&mut self, left: &Expression) -> Result<Expression, ParserError> {
+    // The call starts with the expression called
+    fn parse_expr_call(
+        &mut self,
+        left: &Expression,
+        start: Position,
+    ) -> Result<Expression, ParserError> {
         let arguments = self.parse_call_arguments()?;
 
         Ok(Expression::FunctionCall {
             function: Box::new(left.clone()),
             arguments,
+            span: Location(Span {
+                start,
+                end: self.curr_span.end,
+            }),
         })
     }
 
     fn parse_call_arguments(&mut self) -> Result<Vec<Expression>, ParserError> {
         let mut arguments: Vec<Expression> = vec![];
+        let start = self.curr_index;
 
         if self.peek_token_is(&Token::RParen) {
             self.next_token();
+            self.finish_node(SyntaxKind::ArgumentList, start);
             return Ok(arguments);
         }
 
@@ -253,14 +416,17 @@ impl Parser {
 
         let _ = self.expect_peek(Token::RParen)?;
 
-        return Ok(arguments);
+        self.finish_node(SyntaxKind::ArgumentList, start);
+        Ok(arguments)
     }
 
     fn parse_expr_boolean(&mut self) -> Expression {
+        self.finish_node(SyntaxKind::Literal, self.curr_index);
         Expression::Bool(self.curr_token_is(&Token::True))
     }
 
     fn parse_expr_grouped(&mut self) -> Result<Expression, ParserError> {
+        let start = self.curr_index;
         self.next_token();
 
         let expr = self.parse_expression(Precedence::Lowest)?;
@@ -271,10 +437,12 @@ impl Parser {
                 operator: self.curr_token.clone(),
             })?;
 
+        self.finish_node(SyntaxKind::ParenExpression, start);
         Ok(expr)
     }
 
     fn parse_expr_if(&mut self) -> Result<Expression, ParserError> {
+        let start = self.curr_index;
         let _ = self.expect_peek(Token::LParen)?;
 
         self.next_token();
@@ -293,6 +461,7 @@ impl Parser {
             None
         };
 
+        self.finish_node(SyntaxKind::IfExpression, start);
         Ok(Expression::If {
             condition: Box::new(condition),
             consequence,
@@ -301,6 +470,7 @@ impl Parser {
     }
 
     fn parse_expr_function(&mut self) -> Result<Expression, ParserError> {
+        let start = self.curr_index;
         let _ = self.expect_peek(Token::LParen)?;
 
         let parameters = self.parse_function_parameters()?;
@@ -309,15 +479,67 @@ impl Parser {
 
         let body = self.parse_statement_block();
 
+        self.finish_node(SyntaxKind::FunctionExpression, start);
         Ok(Expression::Function { parameters, body })
     }
 
+    fn parse_expr_try(&mut self) -> Result<Expression, ParserError> {
+        let start = self.curr_index;
+        let _ = self.expect_peek(Token::LBrace)?;
+
+        let body = self.parse_statement_block();
+
+        let catch = if self.peek_token_is(&Token::Catch) {
+            self.next_token();
+            let _ = self.expect_peek(Token::LParen)?;
+
+            let Token::Ident(name) = self.peek_token.clone() else {
+                return Err(ParserError::UnexpectedToken {
+                    expected: Token::Ident("".to_string()),
+                    got: self.peek_token.clone(),
+                });
+            };
+            self.next_token();
+
+            let _ = self.expect_peek(Token::RParen)?;
+            let _ = self.expect_peek(Token::LBrace)?;
+            Some((name, self.parse_statement_block()))
+        } else {
+            None
+        };
+
+        let finally = if self.peek_token_is(&Token::Finally) {
+            self.next_token();
+            let _ = self.expect_peek(Token::LBrace)?;
+            Some(self.parse_statement_block())
+        } else {
+            None
+        };
+
+        // A try alone would do nothing
+        if catch.is_none() && finally.is_none() {
+            return Err(ParserError::UnexpectedToken {
+                expected: Token::Catch,
+                got: self.peek_token.clone(),
+            });
+        }
+
+        self.finish_node(SyntaxKind::TryExpression, start);
+        Ok(Expression::Try {
+            body,
+            catch,
+            finally,
+        })
+    }
+
     fn parse_function_parameters(&mut self) -> Result<Vec<Expression>, ParserError> {
         let mut identifiers: Vec<Expression> = vec![];
+        let start = self.curr_index;
 
         self.next_token();
         // Empty argument list
         if self.curr_token_is(&Token::RParen) {
+            self.finish_node(SyntaxKind::ParameterList, start);
             return Ok(identifiers);
         }
 
@@ -331,6 +553,7 @@ impl Parser {
 
         let _ = self.expect_peek(Token::RParen)?;
 
+        self.finish_node(SyntaxKind::ParameterList, start);
         Ok(identifiers)
     }
 
@@ -343,6 +566,11 @@ impl Parser {
     }
 
     fn expect_peek(&mut self, t: Token) -> Result<Token, ParserError> {
+        // Report the lexer error rather than an unexpected ILLEGAL token
+        if let Token::Illegal(err) = &self.peek_token {
+            return Err(ParserError::Lexer(err.clone()));
+        }
+
         match self.peek_token_is(&t) {
             true => {
                 self.next_token();
@@ -368,25 +596,34 @@ impl Parser {
     }
 }
 
+// Same relative order as in C
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
 pub(crate) enum Precedence {
     Lowest = 1,      // Default
-    Equals = 2,      // == or !=
-    LessGreater = 3, // > or <
-    Sum = 4,         // + or -
-    Product = 5,     // * or /
-    Prefix = 6,      // -x or !x
-    Call = 7,        // fn(x)
+    BitOr = 2,       // |
+    BitXor = 3,      // ^
+    BitAnd = 4,      // &
+    Equals = 5,      // == or !=
+    LessGreater = 6, // > or <
+    Shift = 7,       // << or >>
+    Sum = 8,         // + or -
+    Product = 9,     // * or /
+    Prefix = 10,     // -x or !x or ~x
+    Call = 11,       // fn(x) or x?
 }
 
 impl From<&Token> for Precedence {
     fn from(value: &Token) -> Self {
         match value {
+            Token::Pipe => Precedence::BitOr,
+            Token::Caret => Precedence::BitXor,
+            Token::Ampersand => Precedence::BitAnd,
             Token::Eq | Token::NotEq => Precedence::Equals,
             Token::LessThan | Token::GreaterThan => Precedence::LessGreater,
+            Token::ShiftLeft | Token::ShiftRight => Precedence::Shift,
             Token::Plus | Token::Minus => Precedence::Sum,
             Token::Slash | Token::Asterisk => Precedence::Product,
-            Token::LParen => Precedence::Call,
+            Token::LParen | Token::Question => Precedence::Call,
             _ => Precedence::Lowest,
         }
     }
@@ -1,4 +1,5 @@
 use super::*;
+use crate::lexer::LexErrorKind;
 
 #[test]
 fn let_statements() {
@@ -114,6 +115,101 @@ fn int_expression() {
     }
 }
 
+#[test]
+fn big_int_expression() {
+    let input = "92233720368547758070;";
+
+    let lexer = Lexer::new(input.to_string());
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program();
+    check_parser_errors(parser);
+    assert!(program.is_ok());
+
+    let program = program.unwrap();
+    assert_eq!(program.statements.len(), 1);
+
+    match &program.statements[0] {
+        Statement::Expression(expr) => {
+            assert_eq!(
+                expr,
+                &Expression::BigInt("92233720368547758070".parse().unwrap())
+            );
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn float_expression() {
+    let input = "3.25; 1e-9;";
+
+    let lexer = Lexer::new(input.to_string());
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program();
+    check_parser_errors(parser);
+    assert!(program.is_ok());
+
+    let program = program.unwrap();
+    assert_eq!(
+        program.statements,
+        vec![
+            Statement::Expression(Expression::Float(3.25)),
+            Statement::Expression(Expression::Float(1e-9)),
+        ]
+    );
+}
+
+#[test]
+fn string_expression() {
+    let input = r#""hello world";"#;
+
+    let lexer = Lexer::new(input.to_string());
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program();
+    check_parser_errors(parser);
+    assert!(program.is_ok());
+
+    let program = program.unwrap();
+    assert_eq!(
+        program.statements,
+        vec![Statement::Expression(Expression::Str(
+            "hello world".to_string()
+        ))]
+    );
+}
+
+#[test]
+fn lexer_errors() {
+    let input = r#"let x = 5 @ 3; let y = "\q"; 0b12"#;
+
+    let lexer = Lexer::new(input.to_string());
+    let mut parser = Parser::new(lexer);
+
+    let _ = parser.parse_program();
+
+    // The tokens after an error are parsed again, which can report more errors
+    let kinds: Vec<LexErrorKind> = parser
+        .errors
+        .into_iter()
+        .filter_map(|err| match err {
+            ParserError::Lexer(err) => Some(err.kind),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(
+        kinds,
+        vec![
+            LexErrorKind::UnknownCharacter('@'),
+            LexErrorKind::InvalidEscape('q'),
+            LexErrorKind::MalformedNumber("0b12".to_string()),
+        ]
+    );
+}
+
 #[test]
 fn prefix_expression() {
     let input = "!5; -15;";
@@ -168,6 +264,11 @@ fn infix_expressions() {
         ("5 < 5;", 5, "<", 5),
         ("5 == 5;", 5, "==", 5),
         ("5 != 5;", 5, "!=", 5),
+        ("5 & 5;", 5, "&", 5),
+        ("5 | 5;", 5, "|", 5),
+        ("5 ^ 5;", 5, "^", 5),
+        ("5 << 5;", 5, "<<", 5),
+        ("5 >> 5;", 5, ">>", 5),
     ];
 
     for (input, left, operator, right) in tests {
@@ -226,6 +327,16 @@ fn operator_precedence() {
             "add(a + b + c * d / f + g)",
             "add((((a + b) + ((c * d) / f)) + g))",
         ),
+        ("a | b ^ c & d", "(a | (b ^ (c & d)))"),
+        ("a & b == c", "(a & (b == c))"),
+        ("1 << 2 + 3", "(1 << (2 + 3))"),
+        ("a << 1 < b >> 2", "((a << 1) < (b >> 2))"),
+        ("~a & -b", "((~a) & (-b))"),
+        ("a ^ b | c ^ d", "((a ^ b) | (c ^ d))"),
+        ("a + b? * c", "(a + ((b?) * c))"),
+        ("-f(x)?", "(-(f(x)?))"),
+        ("f(x)?(y)?", "((f(x)?)(y)?)"),
+        ("(a + b)?", "((a + b)?)"),
     ];
 
     for (input, expected) in tests {
@@ -447,8 +558,13 @@ fn function_call() {
                             right: Box::new(Expression::Int(5)),
                         },
                     ],
+                    span: Location(line_span(0, 20)),
                 }
             );
+            let Expression::FunctionCall { span, .. } = expr else {
+                panic!();
+            };
+            assert_eq!(span.0, line_span(0, 20));
         }
         _ => assert!(false),
     }
@@ -456,8 +572,8 @@ fn function_call() {
 
 fn function_call_arguments() {
     let tests = vec![
-        ("add();", vec![]),
-        ("add(1);", vec![Expression::Int(1)]),
+        ("add();", vec![], 5),
+        ("add(1);", vec![Expression::Int(1)], 6),
         (
             "add(1, 2 * 3, 4 + 5);",
             vec![
@@ -473,10 +589,11 @@ fn function_call_arguments() {
                     right: Box::new(Expression::Int(5)),
                 },
             ],
+            20,
         ),
     ];
 
-    for (input, expected) in tests {
+    for (input, expected, end) in tests {
         let lexer = Lexer::new(input.to_string());
         let mut parser = Parser::new(lexer);
 
@@ -494,14 +611,189 @@ fn function_call_arguments() {
                     &Expression::FunctionCall {
                         function: Box::new(Expression::Identifier("add".to_string())),
                         arguments: expected,
+                        span: Location(line_span(0, end)),
                     }
                 );
+                let Expression::FunctionCall { span, .. } = expr else {
+                    panic!();
+                };
+                assert_eq!(span.0, line_span(0, end));
             }
             _ => assert!(false),
         }
     }
 }
 
+#[test]
+fn throw_statement() {
+    let lexer = Lexer::new("throw \"oops\"; throw error(\"Kind\", x)".to_string());
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program().unwrap();
+    check_parser_errors(parser);
+
+    assert_eq!(
+        program.statements,
+        vec![
+            Statement::Throw(Expression::Str("oops".to_string())),
+            Statement::Throw(Expression::FunctionCall {
+                function: Box::new(Expression::Identifier("error".to_string())),
+                arguments: vec![
+                    Expression::Str("Kind".to_string()),
+                    Expression::Identifier("x".to_string()),
+                ],
+                span: Location(line_span(20, 36)),
+            }),
+        ]
+    );
+}
+
+#[test]
+fn try_expression() {
+    let body = || {
+        vec![Statement::Expression(Expression::Identifier(
+            "a".to_string(),
+        ))]
+    };
+    let handler = || vec![Statement::Throw(Expression::Identifier("e".to_string()))];
+    let finally = || vec![Statement::Return(Expression::Int(0))];
+
+    let tests = vec![
+        (
+            "try { a } catch (e) { throw e; }",
+            Some(("e".to_string(), handler())),
+            None,
+        ),
+        ("try { a } finally { return 0; }", None, Some(finally())),
+        (
+            "try { a } catch (e) { throw e } finally { return 0 }",
+            Some(("e".to_string(), handler())),
+            Some(finally()),
+        ),
+    ];
+
+    for (input, catch, finally) in tests {
+        let lexer = Lexer::new(input.to_string());
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program().unwrap();
+        check_parser_errors(parser);
+
+        assert_eq!(
+            program.statements,
+            vec![Statement::Expression(Expression::Try {
+                body: body(),
+                catch,
+                finally,
+            })],
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn try_errors() {
+    let tests = vec![
+        (
+            "try { a }",
+            ParserError::UnexpectedToken {
+                expected: Token::Catch,
+                got: Token::Eof,
+            },
+        ),
+        (
+            "try { a } catch { b }",
+            ParserError::UnexpectedToken {
+                expected: Token::LParen,
+                got: Token::Catch,
+            },
+        ),
+        (
+            "try { a } catch (1) { b }",
+            ParserError::UnexpectedToken {
+                expected: Token::Ident("".to_string()),
+                got: Token::Int(1),
+            },
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let lexer = Lexer::new(input.to_string());
+        let mut parser = Parser::new(lexer);
+        let _ = parser.parse_program();
+
+        assert_eq!(
+            parser.errors.first().map(|err| err.to_string()),
+            Some(expected.to_string()),
+            "{}",
+            input
+        );
+    }
+}
+
+// A prefix operator without a valid operand is a diagnostic, not a panic
+#[test]
+fn prefix_errors() {
+    let tests = vec![
+        (
+            "-@",
+            ParserError::Lexer(LexError {
+                kind: LexErrorKind::UnknownCharacter('@'),
+                position: Position {
+                    offset: 1,
+                    line: 1,
+                    column: 2,
+                },
+            }),
+        ),
+        (
+            "-;",
+            ParserError::InvalidPrefixOperator {
+                operator: Token::Semicolon,
+            },
+        ),
+        (
+            "!",
+            ParserError::InvalidPrefixOperator {
+                operator: Token::Eof,
+            },
+        ),
+        (
+            "let x = -;",
+            ParserError::InvalidPrefixOperator {
+                operator: Token::Semicolon,
+            },
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let lexer = Lexer::new(input.to_string());
+        let mut parser = Parser::new(lexer);
+        let _ = parser.parse_program();
+
+        assert_eq!(
+            parser.errors.first().map(|err| err.to_string()),
+            Some(expected.to_string()),
+            "{}",
+            input
+        );
+    }
+}
+
+// Span between two offsets of the first line of an ASCII source
+fn line_span(start: usize, end: usize) -> Span {
+    let position = |offset| Position {
+        offset,
+        line: 1,
+        column: offset + 1,
+    };
+    Span {
+        start: position(start),
+        end: position(end),
+    }
+}
+
 fn check_parser_errors(parser: Parser) {
     let nb_error = parser.errors.len();
 
@@ -1,4 +1,4 @@
-use crate::{evaluation::Eval, lexer::Lexer, parser::Parser, token::Token, Environment};
+use crate::{emit::Emit, lexer::Lexer, parser::Parser, token::Token, Environment, Limits};
 use std::{
     io::{self, Write},
     rc::Rc,
@@ -9,9 +9,21 @@ use std::{
 pub struct Repl {
     mode: ReplMode,
     environment: Option<Rc<Environment>>,
+    // Apply to each input evaluated
+    limits: Limits,
+    // By the inputs evaluated in the environment
+    allocated: usize,
 }
 
 impl Repl {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    #[allow(clippy::single_match)]
     pub fn start(&mut self) {
         self.swap_mode(self.mode.clone());
 
@@ -37,6 +49,7 @@ impl Repl {
             match self.mode {
                 ReplMode::Lexing => self.lex_input(input),
                 ReplMode::Parsing => self.parse_input(input),
+                ReplMode::Dot => self.dot_input(input),
                 ReplMode::Eval => self.eval_input(input),
             }
         }
@@ -51,6 +64,7 @@ impl Repl {
         self.mode = mode.clone();
         if mode == ReplMode::Eval {
             self.environment = Some(Environment::new_rc());
+            self.allocated = 0;
         } else {
             self.environment = None;
         }
@@ -68,24 +82,28 @@ impl Repl {
         }
     }
 
+    // Prints the AST as an S-expression, with the span of each node
     fn parse_input(&self, input: String) {
-        let mut parser = Parser::new(Lexer::new(input));
+        self.emit_input(Emit::Sexpr, input)
+    }
 
-        println!("Errors {:?}", parser.errors);
+    fn dot_input(&self, input: String) {
+        self.emit_input(Emit::Dot, input)
+    }
 
-        let program = parser.parse_program();
-        let program = match program {
-            Ok(program) => program,
-            Err(e) => {
-                eprintln!("Error parsing program: {:?}", e);
-                return;
+    fn emit_input(&self, emit: Emit, input: String) {
+        match emit.source(input) {
+            Ok(sexpr) => print!("{}", sexpr),
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("{}", error);
+                }
             }
-        };
-
-        println!("Program: {}", program);
+        }
     }
 
-    fn eval_input(&self, input: String) {
+    #[allow(clippy::len_zero)]
+    fn eval_input(&mut self, input: String) {
         let lexer = Lexer::new(input);
         let mut parser = Parser::new(lexer);
         let Ok(program) = parser.parse_program() else {
@@ -94,13 +112,16 @@ impl Repl {
         };
 
         if parser.errors.len() > 0 {
-            eprintln!("Error parsing program");
+            for error in &parser.errors {
+                eprintln!("{}", error);
+            }
             return;
         }
 
-        match program.eval(self.environment.clone().unwrap()) {
+        let environment = self.environment.clone().unwrap();
+        match program.eval_allocated(environment, &self.limits, &mut self.allocated) {
             Ok(object) => println!("{}", object),
-            Err(e) => eprintln!("{:?}", e),
+            Err(e) => eprintln!("{}", e),
         }
     }
 }
@@ -109,6 +130,8 @@ impl Repl {
 pub(crate) enum ReplMode {
     Lexing,
     Parsing,
+    // Prints the AST as a Graphviz digraph
+    Dot,
     #[default]
     Eval,
 }
@@ -121,6 +144,7 @@ impl std::fmt::Display for ReplMode {
             match self {
                 ReplMode::Lexing => "Lexing",
                 ReplMode::Parsing => "Parsing",
+                ReplMode::Dot => "Dot",
                 ReplMode::Eval => "Eval",
             }
         )
@@ -134,6 +158,7 @@ impl FromStr for ReplMode {
         match s.trim().to_lowercase().as_ref() {
             "lexing" => Ok(Self::Lexing),
             "parsing" => Ok(Self::Parsing),
+            "dot" => Ok(Self::Dot),
             "eval" => Ok(Self::Eval),
             _ => Err(()),
         }
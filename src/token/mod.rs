@@ -1,13 +1,20 @@
-#[derive(Clone, Default, Debug, PartialEq, Eq, Hash)]
-pub(crate) enum Token {
+use crate::lexer::LexError;
+use num_bigint::BigInt;
+
+#[derive(Clone, Default, Debug, PartialEq)]
+pub enum Token {
     // Special tokens
-    Illegal(String),
+    Illegal(LexError),
     #[default]
     Eof,
 
     // Identifiers + literals
     Ident(String),
     Int(i64),
+    // Integer literal too large to fit in an i64
+    BigInt(BigInt),
+    Float(f64),
+    Str(String),
 
     // Operators
     Assign,
@@ -22,6 +29,15 @@ pub(crate) enum Token {
     LessThan,
     GreaterThan,
 
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    // Postfix, f()?
+    Question,
+    ShiftLeft,
+    ShiftRight,
+
     // Delimiters
     Comma,
     Semicolon,
@@ -39,6 +55,10 @@ pub(crate) enum Token {
     If,
     Else,
     Return,
+    Throw,
+    Try,
+    Catch,
+    Finally,
 }
 
 impl std::fmt::Display for Token {
@@ -48,6 +68,9 @@ impl std::fmt::Display for Token {
             Token::Eof => "EOF",
             Token::Ident(str) => return write!(f, "{str}"),
             Token::Int(nb) => return write!(f, "{nb}"),
+            Token::BigInt(nb) => return write!(f, "{nb}"),
+            Token::Float(nb) => return write!(f, "{nb:?}"),
+            Token::Str(str) => return write!(f, "{str:?}"),
             Token::Assign => "ASSIGN",
             Token::Plus => "+",
             Token::Minus => "-",
@@ -58,6 +81,13 @@ impl std::fmt::Display for Token {
             Token::NotEq => "!=",
             Token::LessThan => "<",
             Token::GreaterThan => ">",
+            Token::Ampersand => "&",
+            Token::Pipe => "|",
+            Token::Caret => "^",
+            Token::Tilde => "~",
+            Token::Question => "?",
+            Token::ShiftLeft => "<<",
+            Token::ShiftRight => ">>",
             Token::Comma => ",",
             Token::Semicolon => ";",
             Token::LParen => "(",
@@ -71,7 +101,59 @@ impl std::fmt::Display for Token {
             Token::If => "IF",
             Token::Else => "ELSE",
             Token::Return => "RETURN",
+            Token::Throw => "THROW",
+            Token::Try => "TRY",
+            Token::Catch => "CATCH",
+            Token::Finally => "FINALLY",
         };
         write!(f, "{}", token)
     }
 }
+
+// Location of a char in the source, line and column are 1-based
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    // In bytes from the start of the source
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+// Range of source text, from start up to end excluded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+impl Position {
+    // Position right after the text, when it starts at self
+    pub fn advance(&self, text: &str) -> Position {
+        let mut position = Position {
+            offset: self.offset + text.len(),
+            ..*self
+        };
+        for ch in text.chars() {
+            match ch {
+                '\n' => {
+                    position.line += 1;
+                    position.column = 1;
+                }
+                _ => position.column += 1,
+            }
+        }
+        position
+    }
+}
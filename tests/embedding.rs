@@ -0,0 +1,87 @@
+use rust_interpreter::{EvalError, Interpreter, Limits, Object, ProgramError};
+
+#[test]
+fn eval_sources() {
+    let interpreter = Interpreter::default();
+
+    let double = interpreter.eval("let double = fn(x) { x * 2 };").unwrap();
+    assert_eq!(double.object_type(), "FUNCTION");
+    // Bindings are kept from one source to the next
+    assert_eq!(interpreter.eval("double(21)").unwrap(), Object::Integer(42));
+
+    match interpreter.eval("let = 1;") {
+        Err(ProgramError::Parse(errors)) => assert!(!errors.is_empty()),
+        result => panic!("expected a parse error, got {:?}", result),
+    }
+
+    let err = interpreter.eval("double(1) / 0").unwrap_err();
+    assert_eq!(err.to_string(), "error[E0008]: division by zero");
+}
+
+#[test]
+fn eval_with_limits() {
+    let interpreter = Interpreter::new(Limits {
+        max_steps: Some(1000),
+        ..Default::default()
+    });
+
+    assert_eq!(interpreter.eval("1 + 2").unwrap(), Object::Integer(3));
+    match interpreter.eval("let f = fn(n) { f(n + 1) }; f(0)") {
+        Err(ProgramError::Runtime(err)) => {
+            assert_eq!(err.error, EvalError::StepLimit { steps: 1000 });
+            assert_eq!(err.error.code(), "E0010");
+        }
+        result => panic!("expected a step limit error, got {:?}", result),
+    }
+
+    // The limits of one interpreter do not apply to the others
+    let unlimited = Interpreter::default();
+    assert_eq!(
+        unlimited
+            .eval("let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(1000)")
+            .unwrap(),
+        Object::Integer(0)
+    );
+    // Nor on which one ran last on the thread
+    let shallow = Interpreter::new(Limits {
+        max_call_depth: 10,
+        ..Default::default()
+    });
+    assert!(shallow
+        .eval("let f = fn(n) { 1 + f(n + 1) }; f(0)")
+        .is_err());
+    assert_eq!(
+        unlimited
+            .eval("let g = fn(n) { if (n == 0) { 0 } else { 1 + g(n - 1) } }; g(100)")
+            .unwrap(),
+        Object::Integer(100)
+    );
+    assert!(interpreter.eval("f(0)").is_err());
+}
+
+// The bindings kept from one source to the next count against the same budget
+#[test]
+fn allocations_add_up() {
+    let limit = 10_000;
+    let interpreter = Interpreter::new(Limits {
+        max_allocated: Some(limit),
+        ..Default::default()
+    });
+
+    let value = "x".repeat(100);
+    let failed = (0..1000)
+        .position(|i| {
+            interpreter
+                .eval(&format!("let x{} = \"{}\";", i, value))
+                .is_err()
+        })
+        .expect("the budget to run out");
+    assert!(failed < 100, "{}", failed);
+    // Any later binding goes over the budget too
+    match interpreter.eval("let y = 1;") {
+        Err(ProgramError::Runtime(err)) => {
+            assert_eq!(err.error, EvalError::OutOfMemory { limit })
+        }
+        result => panic!("expected an out of memory error, got {:?}", result),
+    }
+}